@@ -0,0 +1,108 @@
+package main
+
+import (
+	"bufio"
+	"crypto/subtle"
+	"fmt"
+	"log"
+	"net/http"
+	"os"
+	"strings"
+	"time"
+
+	"github.com/labstack/echo"
+)
+
+type token struct {
+	secret       string
+	tenant       string
+	scopes       map[string]bool
+	disabledAPIs string
+}
+
+var tokens []token
+
+// authEnabled is set as soon as a tokens file is configured, so a file that
+// yields no tokens can never fall back to running unauthenticated.
+var authEnabled bool
+
+func loadTokens(path string) error {
+	authEnabled = true
+	f, err := os.Open(path)
+	if err != nil {
+		return err
+	}
+	defer f.Close()
+	scanner := bufio.NewScanner(f)
+	for n := 1; scanner.Scan(); n++ {
+		line := strings.TrimSpace(scanner.Text())
+		if line == "" || strings.HasPrefix(line, "#") {
+			continue
+		}
+		fields := strings.Fields(line)
+		if len(fields) != 3 && len(fields) != 4 {
+			return fmt.Errorf("%s:%d: expected \"<token> <tenant> <scope,...> [disabled-api,...]\"", path, n)
+		}
+		t := token{secret: fields[0], tenant: fields[1], scopes: map[string]bool{}}
+		if len(fields) == 4 {
+			t.disabledAPIs = fields[3]
+		}
+		for _, scope := range strings.Split(fields[2], ",") {
+			t.scopes[scope] = true
+		}
+		tokens = append(tokens, t)
+	}
+	if err := scanner.Err(); err != nil {
+		return err
+	}
+	if len(tokens) == 0 {
+		return fmt.Errorf("%s: no tokens", path)
+	}
+	return nil
+}
+
+func lookupToken(secret string) *token {
+	var found *token
+	for i := range tokens {
+		if subtle.ConstantTimeCompare([]byte(tokens[i].secret), []byte(secret)) == 1 {
+			found = &tokens[i]
+		}
+	}
+	return found
+}
+
+func requireScope(scope string) echo.MiddlewareFunc {
+	return func(next echo.HandlerFunc) echo.HandlerFunc {
+		return func(c echo.Context) error {
+			if !authEnabled {
+				return next(c)
+			}
+			header := c.Request().Header.Get("Authorization")
+			var t *token
+			if strings.HasPrefix(header, "Bearer ") {
+				t = lookupToken(strings.TrimPrefix(header, "Bearer "))
+			}
+			if t == nil {
+				c.Response().Header().Set("WWW-Authenticate", "Bearer")
+				return echo.NewHTTPError(http.StatusUnauthorized)
+			}
+			if !t.scopes[scope] && !t.scopes["admin"] {
+				return echo.NewHTTPError(http.StatusForbidden)
+			}
+			c.Set("tenant", t.tenant)
+			disabled := t.disabledAPIs
+			if capability := c.Request().Header.Get("X-Capability-Token"); capability != "" {
+				granted, err := verifyCapability(capability, t.tenant, time.Now())
+				if err != nil {
+					return echo.NewHTTPError(http.StatusForbidden, err.Error())
+				}
+				log.Printf("tenant %s: capability token grants %s", t.tenant, strings.Join(granted, ","))
+				disabled = grantAPIs(disabled, granted)
+			}
+			if disabled != "" {
+				c.Set("disabled_apis", disabled)
+			}
+			return next(c)
+		}
+	}
+}
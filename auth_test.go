@@ -0,0 +1,73 @@
+package main
+
+import (
+	"net/http"
+	"net/http/httptest"
+	"os"
+	"path/filepath"
+	"testing"
+
+	"github.com/labstack/echo"
+)
+
+func withTokens(t *testing.T, lines string) error {
+	path := filepath.Join(t.TempDir(), "tokens")
+	if err := os.WriteFile(path, []byte(lines), 0o600); err != nil {
+		t.Fatal(err)
+	}
+	t.Cleanup(func() {
+		tokens = nil
+		authEnabled = false
+	})
+	return loadTokens(path)
+}
+
+func authorize(t *testing.T, header string) int {
+	req := httptest.NewRequest(http.MethodPost, "/", nil)
+	if header != "" {
+		req.Header.Set("Authorization", header)
+	}
+	rec := httptest.NewRecorder()
+	handler := requireScope("run")(func(c echo.Context) error {
+		return c.String(http.StatusOK, c.Get("tenant").(string))
+	})
+	if err := handler(echo.New().NewContext(req, rec)); err != nil {
+		if he, ok := err.(*echo.HTTPError); ok {
+			return he.Code
+		}
+		t.Fatal(err)
+	}
+	return rec.Code
+}
+
+func TestRequireScopeChecksTheBearerToken(t *testing.T) {
+	if err := withTokens(t, "# tenants\nrunner-secret bot run\nadmin-secret ops admin\nread-secret web schema\n"); err != nil {
+		t.Fatal(err)
+	}
+	cases := []struct {
+		header string
+		want   int
+	}{
+		{"", http.StatusUnauthorized},
+		{"runner-secret", http.StatusUnauthorized},
+		{"Basic runner-secret", http.StatusUnauthorized},
+		{"Bearer wrong-secret", http.StatusUnauthorized},
+		{"Bearer read-secret", http.StatusForbidden},
+		{"Bearer runner-secret", http.StatusOK},
+		{"Bearer admin-secret", http.StatusOK},
+	}
+	for _, c := range cases {
+		if got := authorize(t, c.header); got != c.want {
+			t.Errorf("Authorization %q: got %d, want %d", c.header, got, c.want)
+		}
+	}
+}
+
+func TestEmptyTokensFileIsAnError(t *testing.T) {
+	if err := withTokens(t, "# no tokens yet\n\n"); err == nil {
+		t.Fatal("loadTokens accepted a file without tokens")
+	}
+	if got := authorize(t, ""); got != http.StatusUnauthorized {
+		t.Fatalf("request without a token got %d after a failed load", got)
+	}
+}
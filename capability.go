@@ -0,0 +1,87 @@
+package main
+
+import (
+	"crypto/hmac"
+	"crypto/sha256"
+	"encoding/hex"
+	"errors"
+	"os"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+)
+
+var capabilityKey = []byte(os.Getenv("RUNNER_CAPABILITY_KEY"))
+
+var usedNonces = struct {
+	sync.Mutex
+	expiry map[string]int64
+}{expiry: map[string]int64{}}
+
+// A capability token is "<tenant>.<api,...>.<expiry unix seconds>.<nonce>.<hex HMAC-SHA256 of the first four parts>".
+// It is only valid for the named tenant, and each nonce is accepted once.
+func verifyCapability(tok string, tenant string, now time.Time) ([]string, error) {
+	if len(capabilityKey) == 0 {
+		return nil, errors.New("capability tokens are not enabled")
+	}
+	i := strings.LastIndexByte(tok, '.')
+	if i < 0 {
+		return nil, errors.New("malformed capability token")
+	}
+	payload, sig := tok[:i], tok[i+1:]
+	want, err := hex.DecodeString(sig)
+	if err != nil {
+		return nil, errors.New("malformed capability token")
+	}
+	mac := hmac.New(sha256.New, capabilityKey)
+	mac.Write([]byte(payload))
+	if !hmac.Equal(mac.Sum(nil), want) {
+		return nil, errors.New("invalid capability token signature")
+	}
+	parts := strings.Split(payload, ".")
+	n := len(parts)
+	if n < 4 || parts[n-1] == "" {
+		return nil, errors.New("malformed capability token")
+	}
+	owner, apis, exp, nonce := strings.Join(parts[:n-3], "."), parts[n-3], parts[n-2], parts[n-1]
+	expiry, err := strconv.ParseInt(exp, 10, 64)
+	if err != nil {
+		return nil, errors.New("malformed capability token")
+	}
+	if owner != tenant {
+		return nil, errors.New("capability token was issued to another tenant")
+	}
+	if now.Unix() >= expiry {
+		return nil, errors.New("capability token expired")
+	}
+	usedNonces.Lock()
+	defer usedNonces.Unlock()
+	for used, until := range usedNonces.expiry {
+		if now.Unix() >= until {
+			delete(usedNonces.expiry, used)
+		}
+	}
+	key := tenant + "." + nonce
+	if _, used := usedNonces.expiry[key]; used {
+		return nil, errors.New("capability token already used")
+	}
+	usedNonces.expiry[key] = expiry
+	return strings.Split(apis, ","), nil
+}
+
+func grantAPIs(disabled string, granted []string) string {
+	var kept []string
+	for _, api := range strings.Split(disabled, ",") {
+		allowed := false
+		for _, g := range granted {
+			if g == api {
+				allowed = true
+			}
+		}
+		if api != "" && !allowed {
+			kept = append(kept, api)
+		}
+	}
+	return strings.Join(kept, ",")
+}
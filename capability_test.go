@@ -0,0 +1,41 @@
+package main
+
+import (
+	"crypto/hmac"
+	"crypto/sha256"
+	"encoding/hex"
+	"strconv"
+	"testing"
+	"time"
+)
+
+func mint(payload string) string {
+	mac := hmac.New(sha256.New, capabilityKey)
+	mac.Write([]byte(payload))
+	return payload + "." + hex.EncodeToString(mac.Sum(nil))
+}
+
+func TestCapabilityTokensAreBoundToTenantAndUsedOnce(t *testing.T) {
+	old := capabilityKey
+	capabilityKey = []byte("test-key")
+	t.Cleanup(func() { capabilityKey = old })
+	now := time.Unix(1000, 0)
+	exp := strconv.FormatInt(now.Unix()+60, 10)
+	tok := mint("team.a.fs,search." + exp + ".n1")
+	if _, err := verifyCapability(tok, "other", now); err == nil {
+		t.Fatal("token accepted for another tenant")
+	}
+	granted, err := verifyCapability(tok, "team.a", now)
+	if err != nil || len(granted) != 2 || granted[0] != "fs" || granted[1] != "search" {
+		t.Fatalf("granted %v, %v", granted, err)
+	}
+	if _, err := verifyCapability(tok, "team.a", now); err == nil {
+		t.Fatal("token replayed")
+	}
+	if _, err := verifyCapability(mint("team.a.fs."+exp+".n2"), "team.a", now.Add(time.Hour)); err == nil {
+		t.Fatal("expired token accepted")
+	}
+	if _, err := verifyCapability("team.a.fs,search."+exp+".n3.00", "team.a", now); err == nil {
+		t.Fatal("bad signature accepted")
+	}
+}
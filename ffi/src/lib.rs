@@ -0,0 +1,95 @@
+use bot_script_runner::config::Config;
+use bot_script_runner::protocol::{self, ErrorCode, ErrorKind, ScriptResult};
+use bot_script_runner::{engine, error};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+unsafe fn c_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+/// # Safety
+///
+/// `argv` must point to `argc` valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn bsr_init(argv: *const *const c_char, argc: c_int) -> c_int {
+    let mut args = Vec::new();
+    for i in 0..argc.max(0) as usize {
+        match c_str(*argv.add(i)) {
+            Some(arg) => args.push(arg.to_string()),
+            None => return ErrorKind::Protocol.exit_code(),
+        }
+    }
+    let config = match Config::from_args(args.into_iter()) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            return ErrorKind::Protocol.exit_code();
+        }
+    };
+    if CONFIG.set(config).is_err() {
+        error!("bsr_init called after the runner was configured");
+        return ErrorKind::Protocol.exit_code();
+    }
+    if let Err(e) = bot_script_runner::initialize(&crate::config().v8_flags) {
+        error!("{}", e);
+        return ErrorKind::Protocol.exit_code();
+    }
+    0
+}
+
+/// # Safety
+///
+/// `json_in` must be a valid NUL-terminated string and `json_out` a valid pointer.
+/// The string stored in `*json_out` must be released with `bsr_free`.
+#[no_mangle]
+pub unsafe extern "C" fn bsr_run(json_in: *const c_char, json_out: *mut *mut c_char) -> c_int {
+    if json_out.is_null() {
+        return ErrorKind::Protocol.exit_code();
+    }
+    let queued_at = Instant::now();
+    let config = config();
+    let res = match bot_script_runner::initialize(&[]) {
+        Err(e) => {
+            error!("{}", e);
+            ScriptResult::err(ErrorCode::Internal, "Internal error")
+        }
+        Ok(()) => match c_str(json_in) {
+            None => ScriptResult::err(ErrorCode::InvalidInput, "Invalid input"),
+            Some(json) => match protocol::read_input(json.as_bytes(), config.max_script_bytes) {
+                Err(e) => ScriptResult::from(e),
+                Ok(mut input) => {
+                    input.queued_at = Some(queued_at);
+                    match engine::select(config.engine) {
+                        Some(engine) => protocol::contain(|| engine::run(&*engine, &input, config, None)).unwrap_or_else(ScriptResult::from),
+                        None => ScriptResult::err(ErrorCode::BadArgs, "Error"),
+                    }
+                }
+            },
+        },
+    };
+    let doc = serde_json::to_string(&res).unwrap_or_else(|_| r#"{"result":"","error":"Error"}"#.to_string());
+    *json_out = CString::new(doc).map_or(std::ptr::null_mut(), CString::into_raw);
+    res.error_kind.map_or(0, ErrorKind::exit_code)
+}
+
+/// # Safety
+///
+/// `s` must be null or a string returned through `bsr_run`, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn bsr_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
@@ -0,0 +1,39 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+
+extern crate bot_script_runner_ffi;
+
+extern "C" {
+    fn bsr_init(argv: *const *const c_char, argc: c_int) -> c_int;
+    fn bsr_run(json_in: *const c_char, json_out: *mut *mut c_char) -> c_int;
+    fn bsr_free(s: *mut c_char);
+}
+
+fn run(input: &str) -> (c_int, serde_json::Value) {
+    let input = CString::new(input).unwrap();
+    let mut out = std::ptr::null_mut();
+    unsafe {
+        let code = bsr_run(input.as_ptr(), &mut out);
+        let doc = serde_json::from_slice(CStr::from_ptr(out).to_bytes()).unwrap();
+        bsr_free(out);
+        (code, doc)
+    }
+}
+
+#[test]
+fn runs_through_the_c_abi() {
+    let (code, doc) = run(r#"{"script":"6 * 7"}"#);
+    assert_eq!(code, 0);
+    assert_eq!(doc["result"], "42");
+    let (code, doc) = run("nope");
+    assert_eq!(code, 2);
+    assert_eq!(doc["error_code"], "E_INVALID_INPUT");
+}
+
+#[test]
+fn init_after_the_first_run_is_rejected() {
+    run(r#"{"script":"1"}"#);
+    let args = [CString::new("--v8-flags=--jitless").unwrap()];
+    let argv: Vec<_> = args.iter().map(|a| a.as_ptr()).collect();
+    assert_eq!(unsafe { bsr_init(argv.as_ptr(), argv.len() as c_int) }, 2);
+}
@@ -0,0 +1,99 @@
+package main
+
+import (
+	"context"
+	"encoding/json"
+	"fmt"
+	"net/http"
+	"os"
+	"os/exec"
+	"strconv"
+	"strings"
+	"sync"
+	"sync/atomic"
+	"time"
+
+	"github.com/labstack/echo"
+)
+
+const probeTimeout = 2 * time.Second
+
+// probeTTL bounds how often /readyz spawns a runner; callers in between share
+// the last result, and concurrent callers wait for the probe in progress.
+const probeTTL = 5 * time.Second
+
+var probeCache struct {
+	sync.Mutex
+	at  time.Time
+	err error
+}
+
+var inFlight int64
+
+var readyWatermark int64 = 64
+
+func loadReadyWatermark() {
+	if v := os.Getenv("RUNNER_READY_WATERMARK"); v != "" {
+		if n, err := strconv.ParseInt(v, 10, 64); err == nil && n > 0 {
+			readyWatermark = n
+		}
+	}
+}
+
+func probeRunner() error {
+	path, err := runnerPath()
+	if err != nil {
+		return err
+	}
+	ctx, cancel := context.WithTimeout(context.Background(), probeTimeout)
+	defer cancel()
+	cmd := exec.CommandContext(ctx, path, os.Args[1:]...)
+	cmd.Stdin = strings.NewReader(`{"script":"1"}`)
+	out, err := cmd.Output()
+	if err != nil {
+		return fmt.Errorf("probe failed: %v", err)
+	}
+	var res struct {
+		Result json.RawMessage `json:"result"`
+		Error  string          `json:"error"`
+	}
+	if err := json.Unmarshal(out, &res); err != nil {
+		return fmt.Errorf("probe returned invalid output: %v", err)
+	}
+	if res.Error != "" {
+		return fmt.Errorf("probe failed: %s", res.Error)
+	}
+	return nil
+}
+
+func cachedProbe() error {
+	probeCache.Lock()
+	defer probeCache.Unlock()
+	if probeCache.at.IsZero() || time.Since(probeCache.at) >= probeTTL {
+		probeCache.err = probeRunner()
+		probeCache.at = time.Now()
+	}
+	return probeCache.err
+}
+
+func healthz(c echo.Context) error {
+	return c.JSON(http.StatusOK, map[string]string{"status": "ok"})
+}
+
+func readyz(c echo.Context) error {
+	checks := map[string]string{"runner": "ok", "queue": "ok"}
+	status := http.StatusOK
+	if err := cachedProbe(); err != nil {
+		checks["runner"] = err.Error()
+		status = http.StatusServiceUnavailable
+	}
+	if n := atomic.LoadInt64(&inFlight); n >= readyWatermark {
+		checks["queue"] = fmt.Sprintf("%d requests in flight", n)
+		status = http.StatusServiceUnavailable
+	}
+	state := "ok"
+	if status != http.StatusOK {
+		state = "unavailable"
+	}
+	return c.JSON(status, map[string]interface{}{"status": state, "checks": checks})
+}
@@ -0,0 +1,36 @@
+package main
+
+import (
+	"net/http"
+	"net/http/httptest"
+	"os"
+	"path/filepath"
+	"strings"
+	"testing"
+	"time"
+
+	"github.com/labstack/echo"
+)
+
+func TestReadyzReusesTheProbeResult(t *testing.T) {
+	log := filepath.Join(t.TempDir(), "probes")
+	fakeRunner(t, `echo probe >> "`+log+`"; echo '{"result":"1","error":""}'`)
+	probeCache.at = time.Time{}
+	t.Cleanup(func() { probeCache.at = time.Time{} })
+	for i := 0; i < 3; i++ {
+		rec := httptest.NewRecorder()
+		if err := readyz(echo.New().NewContext(httptest.NewRequest(http.MethodGet, "/readyz", nil), rec)); err != nil {
+			t.Fatal(err)
+		}
+		if rec.Code != http.StatusOK {
+			t.Fatalf("readyz returned %d: %s", rec.Code, rec.Body.String())
+		}
+	}
+	out, err := os.ReadFile(log)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if n := strings.Count(string(out), "probe"); n != 1 {
+		t.Fatalf("runner probed %d times", n)
+	}
+}
@@ -0,0 +1,22 @@
+#ifndef BOT_SCRIPT_RUNNER_H
+#define BOT_SCRIPT_RUNNER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Configure the runner with the same arguments the binary accepts.
+ * Optional; must be called before the first bsr_run. Returns 0 on success. */
+int bsr_init(const char *const *argv, int argc);
+
+/* Run one input document. On return *json_out holds the result document,
+ * to be released with bsr_free. Returns the binary's exit code. */
+int bsr_run(const char *json_in, char **json_out);
+
+void bsr_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
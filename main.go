@@ -1,70 +1,227 @@
 package main
 
 import (
-	"bufio"
+	"compress/gzip"
+	"compress/zlib"
+	"crypto/rand"
+	_ "embed"
+	"encoding/hex"
 	"encoding/json"
 	"io"
 	"log"
 	"net/http"
+	"os"
 	"os/exec"
 	"path/filepath"
 	"runtime"
+	"strconv"
+	"strings"
+	"sync/atomic"
 	"time"
 
 	"github.com/labstack/echo"
 )
 
-type Script struct {
-	Script string `json:"script"`
-}
-
 type Result struct {
-	Result string `json:"result"`
-	Error  string `json:"error"`
+	Result    string `json:"result"`
+	Error     string `json:"error"`
+	ErrorKind string `json:"error_kind,omitempty"`
+	ErrorCode string `json:"error_code,omitempty"`
+	RequestID string `json:"request_id,omitempty"`
 }
 
+const envelopeBytes = 64 << 10
+
+//go:embed src/schema.json
+var inputSchema []byte
+
+var maxBodyBytes int64 = 1<<20 + envelopeBytes
+
 func main() {
+	for _, arg := range os.Args[1:] {
+		if v := strings.TrimPrefix(arg, "--max-script-bytes="); v != arg {
+			if n, err := strconv.ParseInt(v, 10, 64); err == nil && n >= 0 {
+				maxBodyBytes = n + envelopeBytes
+			}
+		}
+	}
+	if path := os.Getenv("RUNNER_TOKENS_FILE"); path != "" {
+		if err := loadTokens(path); err != nil {
+			log.Fatal(err)
+		}
+	} else {
+		log.Print("RUNNER_TOKENS_FILE is not set, authentication is disabled")
+	}
+	loadReadyWatermark()
 	e := echo.New()
 	e.GET("/", func(c echo.Context) error {
 		return c.String(http.StatusOK, "200 OK")
 	})
-	e.POST("/", run)
-	e.Logger.Fatal(e.Start(":7690"))
+	e.GET("/healthz", healthz)
+	e.GET("/readyz", readyz)
+	e.GET("/schema", func(c echo.Context) error {
+		return c.Blob(http.StatusOK, "application/schema+json", inputSchema)
+	})
+	e.POST("/", run, requireScope("run"))
+	cert, key := os.Getenv("RUNNER_TLS_CERT"), os.Getenv("RUNNER_TLS_KEY")
+	if cert == "" && key == "" {
+		e.Logger.Fatal(e.Start(":7690"))
+	}
+	tlsConfig, err := loadTLSConfig(cert, key, os.Getenv("RUNNER_TLS_CLIENT_CA"))
+	if err != nil {
+		log.Fatal(err)
+	}
+	e.Logger.Fatal(e.StartServer(&http.Server{Addr: ":7690", TLSConfig: tlsConfig}))
 }
 
-func run(c echo.Context) error {
-	cmd_name := "./target/release/bot_script_runner"
-	timeout := false
-	s := new(Script)
-	if err := c.Bind(s); err != nil {
-		return err
+func decompressBody(c echo.Context) error {
+	req := c.Request()
+	var body io.ReadCloser
+	switch req.Header.Get("Content-Encoding") {
+	case "", "identity":
+		return nil
+	case "gzip":
+		r, err := gzip.NewReader(req.Body)
+		if err != nil {
+			return err
+		}
+		body = r
+	case "deflate":
+		r, err := zlib.NewReader(req.Body)
+		if err != nil {
+			return err
+		}
+		body = r
+	default:
+		return echo.ErrUnsupportedMediaType
 	}
-	input, err := json.Marshal(s)
+	req.Header.Del("Content-Encoding")
+	req.ContentLength = -1
+	req.Body = http.MaxBytesReader(c.Response(), body, maxBodyBytes)
+	return nil
+}
+
+func respond(c echo.Context, status int, v interface{}) error {
+	if !strings.Contains(c.Request().Header.Get("Accept-Encoding"), "gzip") {
+		return c.JSON(status, v)
+	}
+	body, err := json.Marshal(v)
 	if err != nil {
 		return err
 	}
+	res := c.Response()
+	res.Header().Set("Content-Encoding", "gzip")
+	res.Header().Set("Content-Type", echo.MIMEApplicationJSONCharsetUTF8)
+	res.Header().Add("Vary", "Accept-Encoding")
+	res.WriteHeader(status)
+	gz := gzip.NewWriter(res)
+	defer gz.Close()
+	_, err = gz.Write(body)
+	return err
+}
+
+func scriptTooLarge(c echo.Context) error {
+	result := new(Result)
+	result.Error = "Script too large"
+	result.ErrorKind = "protocol"
+	result.ErrorCode = "E_SCRIPT_TOO_LARGE"
+	return respond(c, http.StatusRequestEntityTooLarge, result)
+}
+
+func newRequestID() string {
+	b := make([]byte, 8)
+	if _, err := rand.Read(b); err != nil {
+		return strconv.FormatInt(time.Now().UnixNano(), 36)
+	}
+	return hex.EncodeToString(b)
+}
 
+var runnerPath = func() (string, error) {
+	cmd_name := "./target/release/bot_script_runner"
 	if runtime.GOOS == "windows" {
 		cmd_name = ".\\target\\release\\bot_script_runner.exe"
 	}
+	return filepath.Abs(cmd_name)
+}
 
-	cmdPath, err := filepath.Abs(cmd_name)
+func run(c echo.Context) error {
+	atomic.AddInt64(&inFlight, 1)
+	defer atomic.AddInt64(&inFlight, -1)
+	timeout := false
+	if c.Request().ContentLength > maxBodyBytes {
+		return scriptTooLarge(c)
+	}
+	c.Request().Body = http.MaxBytesReader(c.Response(), c.Request().Body, maxBodyBytes)
+	if err := decompressBody(c); err != nil {
+		return echo.NewHTTPError(http.StatusBadRequest, err.Error())
+	}
+	s := map[string]interface{}{}
+	dec := json.NewDecoder(c.Request().Body)
+	dec.UseNumber()
+	if err := dec.Decode(&s); err != nil {
+		if strings.Contains(err.Error(), "request body too large") {
+			return scriptTooLarge(c)
+		}
+		return echo.NewHTTPError(http.StatusBadRequest, err.Error())
+	}
+	requestID := c.Request().Header.Get("X-Request-Id")
+	if id, ok := s["request_id"].(string); ok && id != "" {
+		requestID = id
+	}
+	if requestID == "" {
+		requestID = newRequestID()
+	}
+	s["request_id"] = requestID
+	c.Response().Header().Set("X-Request-Id", requestID)
+	killAfter := 300 * time.Millisecond
+	if n, ok := s["deadline_unix_ms"].(json.Number); ok {
+		ms, _ := n.Float64()
+		remaining := time.Until(time.UnixMilli(int64(ms)))
+		if remaining <= 0 {
+			result := new(Result)
+			result.Error = "Deadline exceeded"
+			result.ErrorKind = "timeout"
+			result.ErrorCode = "E_DEADLINE"
+			return respond(c, http.StatusOK, result)
+		}
+		if remaining < killAfter {
+			killAfter = remaining
+		}
+	}
+	input, err := json.Marshal(s)
 	if err != nil {
 		return err
 	}
 
-	cmd := exec.Command(cmdPath)
+	cmdPath, err := runnerPath()
+	if err != nil {
+		return err
+	}
+
+	args := append([]string{}, os.Args[1:]...)
+	if apis, ok := c.Get("disabled_apis").(string); ok {
+		args = append(args, "--disabled-apis="+apis)
+	}
+	cmd := exec.Command(cmdPath, args...)
+	cmd.Stderr = os.Stderr
 	stdout, err := cmd.StdoutPipe()
 	if err != nil {
 		return err
 	}
-	stdin, _ := cmd.StdinPipe()
-	io.WriteString(stdin, string(input))
-	stdin.Close()
+	stdin, err := cmd.StdinPipe()
+	if err != nil {
+		return err
+	}
 	if err = cmd.Start(); err != nil {
 		return err
 	}
-	ticker := *time.NewTicker(300 * time.Millisecond)
+	go func() {
+		defer stdin.Close()
+		if _, err := stdin.Write(input); err != nil {
+			log.Printf("[%s] stdin error: %v", requestID, err)
+		}
+	}()
+	ticker := *time.NewTicker(killAfter)
 	exit := make(chan bool, 2)
 	var result_str string
 	go func() {
@@ -74,7 +231,7 @@ func run(c echo.Context) error {
 				ticker.Stop()
 				if cmd.ProcessState == nil || !cmd.ProcessState.Exited() {
 					if err := cmd.Process.Kill(); err != nil {
-						log.Print("Stop Error")
+						log.Printf("[%s] Stop Error", requestID)
 					}
 					timeout = true
 					exit <- true
@@ -85,29 +242,35 @@ func run(c echo.Context) error {
 	}()
 	go func() {
 		defer func() { exit <- false }()
-		scanner := bufio.NewScanner(stdout)
-		for scanner.Scan() {
-			result_str += scanner.Text()
+		out, err := io.ReadAll(stdout)
+		if err != nil {
+			log.Printf("[%s] %v", requestID, err)
 		}
+		result_str = string(out)
 		if err := cmd.Wait(); err != nil {
-			log.Print("cmd error")
-			log.Print(err)
+			if _, ok := err.(*exec.ExitError); !ok {
+				log.Printf("[%s] cmd error: %v", requestID, err)
+			}
 		}
 	}()
 	isKill := <-exit
-	result := new(Result)
-	result.Result = ""
-	result.Error = ""
 	if isKill {
+		result := new(Result)
 		result.Error = "Error"
+		result.ErrorKind = "internal"
+		result.ErrorCode = "E_INTERNAL"
+		result.RequestID = requestID
 		if timeout {
 			result.Error = "Timeout"
+			result.ErrorKind = "timeout"
+			result.ErrorCode = "E_TIMEOUT"
 		}
-	} else {
-		if err := json.Unmarshal([]byte(result_str), &result); err != nil {
-			return err
-		}
+		return respond(c, http.StatusOK, result)
+	}
+	var result json.RawMessage
+	if err := json.Unmarshal([]byte(result_str), &result); err != nil {
+		return err
 	}
 
-	return c.JSON(http.StatusOK, result)
+	return respond(c, http.StatusOK, result)
 }
@@ -0,0 +1,57 @@
+package main
+
+import (
+	"encoding/json"
+	"net/http"
+	"net/http/httptest"
+	"os"
+	"path/filepath"
+	"strings"
+	"testing"
+
+	"github.com/labstack/echo"
+)
+
+func post(t *testing.T, body string) *httptest.ResponseRecorder {
+	req := httptest.NewRequest(http.MethodPost, "/", strings.NewReader(body))
+	req.Header.Set(echo.HeaderContentType, echo.MIMEApplicationJSON)
+	rec := httptest.NewRecorder()
+	if err := run(echo.New().NewContext(req, rec)); err != nil {
+		t.Fatal(err)
+	}
+	return rec
+}
+
+func fakeRunner(t *testing.T, script string) {
+	path := filepath.Join(t.TempDir(), "runner")
+	if err := os.WriteFile(path, []byte("#!/bin/sh\n"+script+"\n"), 0o755); err != nil {
+		t.Fatal(err)
+	}
+	old := runnerPath
+	runnerPath = func() (string, error) { return path, nil }
+	t.Cleanup(func() { runnerPath = old })
+}
+
+func TestRunStreamsLargeBodiesToTheRunner(t *testing.T) {
+	fakeRunner(t, "exec cat")
+	script := strings.Repeat("x", 200<<10)
+	body, _ := json.Marshal(map[string]string{"script": script})
+	rec := post(t, string(body))
+	var echoed map[string]interface{}
+	if err := json.Unmarshal(rec.Body.Bytes(), &echoed); err != nil {
+		t.Fatal(err)
+	}
+	if echoed["script"] != script {
+		t.Fatalf("runner received a %d byte script", len(echoed["script"].(string)))
+	}
+}
+
+func TestRunForwardsLargeIntegersExactly(t *testing.T) {
+	fakeRunner(t, "exec cat")
+	rec := post(t, `{"script":"1","random_seed":9007199254740993,"args":[12345678901234567890]}`)
+	for _, want := range []string{`"random_seed":9007199254740993`, `"args":[12345678901234567890]`} {
+		if !strings.Contains(rec.Body.String(), want) {
+			t.Fatalf("%s missing from %s", want, rec.Body.String())
+		}
+	}
+}
@@ -0,0 +1,144 @@
+use crate::config::Config;
+use crate::engine;
+use crate::protocol::Input;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+const DEFAULT_ITERATIONS: usize = 20;
+
+const CORPUS: &[(&str, &str)] = &[
+    ("empty", "''"),
+    ("arithmetic", "let s = 0; for (let i = 0; i < 1e6; i++) s += i % 7; s"),
+    ("strings", "let s = ''; for (let i = 0; i < 10000; i++) s += i.toString(36); s.length"),
+    ("json", "const o = { a: [1, 2, 3], b: { c: 'd' } }; let n = 0; for (let i = 0; i < 10000; i++) n += JSON.stringify(JSON.parse(JSON.stringify(o))).length; n"),
+    ("sort", "const a = []; for (let i = 0; i < 100000; i++) a.push((i * 7919) % 100003); a.sort((x, y) => x - y)[500]"),
+    ("regexp", "const t = 'the quick brown fox '.repeat(1000); (t.match(/\\b\\w{5}\\b/g) || []).length"),
+    ("objects", "const m = new Map(); for (let i = 0; i < 50000; i++) m.set('k' + i, { i }); m.size"),
+    ("format", "format.table([['alice', '10'], ['bob', '7']], { header: ['name', 'score'] })"),
+];
+
+pub struct Options {
+    dir: Option<PathBuf>,
+    iterations: usize,
+}
+
+pub fn split_args(args: Vec<String>) -> Result<(Option<Options>, Vec<String>), String> {
+    if args.first().map(String::as_str) != Some("bench") {
+        return Ok((None, args));
+    }
+    let mut options = Options { dir: None, iterations: DEFAULT_ITERATIONS };
+    let mut rest = Vec::new();
+    for arg in args.into_iter().skip(1) {
+        if let Some(dir) = arg.strip_prefix("--dir=") {
+            options.dir = Some(PathBuf::from(dir));
+        } else if let Some(n) = arg.strip_prefix("--iterations=") {
+            match n.parse::<usize>() {
+                Ok(n) if n > 0 => options.iterations = n,
+                _ => return Err(format!("invalid value for --iterations: {}", n)),
+            }
+        } else {
+            rest.push(arg);
+        }
+    }
+    Ok((Some(options), rest))
+}
+
+#[derive(Serialize)]
+struct ScriptReport {
+    name: String,
+    runs: usize,
+    errors: usize,
+    p50_ms: f64,
+    p95_ms: f64,
+    mean_ms: f64,
+    max_heap_bytes: usize,
+}
+
+#[derive(Serialize)]
+struct Report {
+    iterations: usize,
+    runs: usize,
+    elapsed_ms: f64,
+    throughput_per_s: f64,
+    scripts: Vec<ScriptReport>,
+}
+
+fn load(options: &Options) -> Result<Vec<(String, String)>, String> {
+    let dir = match &options.dir {
+        Some(dir) => dir,
+        None => return Ok(CORPUS.iter().map(|(n, s)| (n.to_string(), s.to_string())).collect()),
+    };
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("failed to read {}: {}", dir.display(), e))?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "js"))
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        return Err(format!("no .js files in {}", dir.display()));
+    }
+    paths
+        .into_iter()
+        .map(|p| {
+            let script = std::fs::read_to_string(&p).map_err(|e| format!("failed to read {}: {}", p.display(), e))?;
+            let name = p.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            Ok((name, script))
+        })
+        .collect()
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> f64 {
+    let rank = ((p * sorted.len() as f64).ceil() as usize).max(1);
+    ms(sorted[rank - 1])
+}
+
+pub fn run(options: &Options, config: &Config) -> Result<(), String> {
+    let engine = engine::select(config.engine).ok_or_else(|| format!("engine not available: {:?}", config.engine))?;
+    let scripts = load(options)?;
+    let start = Instant::now();
+    let mut reports = Vec::new();
+    for (name, script) in scripts {
+        let input: Input = serde_json::from_value(serde_json::json!({ "script": script })).map_err(|e| e.to_string())?;
+        let mut times = Vec::with_capacity(options.iterations);
+        let mut errors = 0;
+        let mut max_heap_bytes = 0;
+        for _ in 0..options.iterations {
+            let t = Instant::now();
+            let res = engine::run(&*engine, &input, config, None);
+            times.push(t.elapsed());
+            if res.error_kind.is_some() {
+                errors += 1;
+            }
+            max_heap_bytes = max_heap_bytes.max(res.stats.heap_bytes);
+        }
+        if errors > 0 {
+            warn!("{}: {} of {} runs failed", name, errors, options.iterations);
+        }
+        times.sort();
+        reports.push(ScriptReport {
+            name,
+            runs: times.len(),
+            errors,
+            p50_ms: percentile(&times, 0.5),
+            p95_ms: percentile(&times, 0.95),
+            mean_ms: ms(times.iter().sum::<Duration>()) / times.len() as f64,
+            max_heap_bytes,
+        });
+    }
+    let elapsed = start.elapsed();
+    let runs = reports.iter().map(|r| r.runs).sum::<usize>();
+    let report = Report {
+        iterations: options.iterations,
+        runs,
+        elapsed_ms: ms(elapsed),
+        throughput_per_s: runs as f64 / elapsed.as_secs_f64(),
+        scripts: reports,
+    };
+    println!("{}", serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?);
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use crate::config::Config;
+use crate::engine::{calls_result, elapsed_ms, unknown_entry, Cancel, Capabilities, Engine, HANDLERS};
+use crate::protocol::{ErrorCode, ExecError, Input, ScriptResult, Stats};
+use boa_engine::{js_string, Context, JsError, JsObject, JsResult, JsValue, Script, Source};
+use serde_json::Value;
+use std::time::Instant;
+
+const RECURSION_LIMIT: usize = 1000;
+
+pub struct BoaEngine;
+
+fn script_error(ctx: &mut Context, err: JsError, code: ErrorCode) -> ExecError {
+    let message = match err.try_native(ctx) {
+        Ok(native) if native.message().is_empty() => format!("Uncaught {}", native.kind()),
+        Ok(native) => format!("Uncaught {}: {}", native.kind(), native.message()),
+        Err(_) => format!("Uncaught {}", err),
+    };
+    ExecError::new(code, message.lines().next().unwrap_or("").to_string())
+}
+
+fn global_function(ctx: &mut Context, name: &str) -> JsResult<Option<JsObject>> {
+    let value = ctx.global_object().get(js_string!(name), ctx)?;
+    Ok(value.as_callable())
+}
+
+fn call_entry(ctx: &mut Context, entry: Option<&str>, args: &[Value], completion: JsValue) -> Result<JsValue, ExecError> {
+    let exception = |ctx: &mut Context, e| script_error(ctx, e, ErrorCode::Exception);
+    let handler = match entry {
+        Some(entry) => Some(global_function(ctx, entry).map_err(|e| exception(ctx, e))?.ok_or_else(|| unknown_entry(entry))?),
+        None => {
+            let mut found = None;
+            for name in HANDLERS {
+                found = global_function(ctx, name).map_err(|e| exception(ctx, e))?;
+                if found.is_some() {
+                    break;
+                }
+            }
+            found
+        }
+    };
+    let handler = match handler {
+        Some(handler) => handler,
+        None => return Ok(completion),
+    };
+    let args = args.iter().map(|arg| JsValue::from_json(arg, ctx)).collect::<JsResult<Vec<_>>>();
+    let args = args.map_err(|e| exception(ctx, e))?;
+    handler.call(&JsValue::undefined(), &args, ctx).map_err(|e| exception(ctx, e))
+}
+
+fn run_script(ctx: &mut Context, input: &Input, config: &Config, stats: &mut Stats) -> Result<Value, ExecError> {
+    let started = Instant::now();
+    let source = match &config.wrapper {
+        Some(wrapper) => wrapper.apply(&input.script),
+        None => input.script.clone(),
+    };
+    let script = Script::parse(Source::from_bytes(&source), None, ctx);
+    stats.compile_ms = elapsed_ms(started);
+    let script = script.map_err(|e| script_error(ctx, e, ErrorCode::Syntax))?;
+    let started = Instant::now();
+    let result = match script.evaluate(ctx) {
+        Ok(value) if input.calls.is_empty() => call_entry(ctx, input.entry.as_deref(), &input.args, value).and_then(|value| encode_result(ctx, config, value, stats)),
+        Ok(value) => run_calls(ctx, input, config, value, stats),
+        Err(e) => Err(script_error(ctx, e, ErrorCode::Exception)),
+    };
+    stats.exec_ms = elapsed_ms(started) - stats.serialize_ms;
+    result
+}
+
+fn run_calls(ctx: &mut Context, input: &Input, config: &Config, completion: JsValue, stats: &mut Stats) -> Result<Value, ExecError> {
+    let mut results = Vec::with_capacity(input.calls.len());
+    for call in &input.calls {
+        let value = call_entry(ctx, Some(&call.entry), &call.args, completion.clone())?;
+        results.push(encode_result(ctx, config, value, stats)?);
+    }
+    calls_result(results, config.max_result_bytes)
+}
+
+fn encode_result(ctx: &mut Context, config: &Config, value: JsValue, stats: &mut Stats) -> Result<Value, ExecError> {
+    let started = Instant::now();
+    let text = value.to_string(ctx).map_err(|e| script_error(ctx, e, ErrorCode::Exception))?;
+    let text = text.to_std_string_escaped();
+    stats.serialize_ms += elapsed_ms(started);
+    if text.len() > config.max_result_bytes {
+        return Err(ExecError::new(ErrorCode::ResultTooLarge, "Result too large"));
+    }
+    Ok(Value::String(text))
+}
+
+impl Engine for BoaEngine {
+    fn name(&self) -> &'static str {
+        "boa"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            calc: false,
+            chart: false,
+            console: false,
+            ctx: false,
+            data: false,
+            datasets: false,
+            format: false,
+            random: false,
+            search: false,
+            extended_results: false,
+            alloc_profile: false,
+            host_trace: false,
+            fs: false,
+            heap_limit: false,
+            timeout: false,
+        }
+    }
+
+    fn exec(&self, input: &Input, config: &Config, _cancel: Option<&Cancel>) -> ScriptResult {
+        let mut ctx = Context::default();
+        ctx.runtime_limits_mut().set_loop_iteration_limit(config.boa_loop_limit);
+        ctx.runtime_limits_mut().set_recursion_limit(RECURSION_LIMIT);
+        let mut stats = Stats {
+            queued_ms: input.queued_at.map_or(0.0, elapsed_ms),
+            ..Stats::default()
+        };
+        let mut res = ScriptResult::from(run_script(&mut ctx, input, config, &mut stats));
+        res.stats = stats;
+        res
+    }
+}
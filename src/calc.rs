@@ -0,0 +1,334 @@
+use crate::host;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+const MAX_EXPONENT: u64 = 1000;
+const MAX_DEPTH: usize = 256;
+const OUTPUT_DP: u32 = 20;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Dim {
+    Length,
+    Mass,
+    Time,
+    Volume,
+    Temperature,
+}
+
+struct Unit {
+    names: &'static [&'static str],
+    dim: Dim,
+    factor: &'static str,
+    offset: &'static str,
+}
+
+const UNITS: &[Unit] = &[
+    Unit { names: &["mm"], dim: Dim::Length, factor: "0.001", offset: "0" },
+    Unit { names: &["cm"], dim: Dim::Length, factor: "0.01", offset: "0" },
+    Unit { names: &["m"], dim: Dim::Length, factor: "1", offset: "0" },
+    Unit { names: &["km"], dim: Dim::Length, factor: "1000", offset: "0" },
+    Unit { names: &["in", "inch"], dim: Dim::Length, factor: "0.0254", offset: "0" },
+    Unit { names: &["ft", "feet"], dim: Dim::Length, factor: "0.3048", offset: "0" },
+    Unit { names: &["yd"], dim: Dim::Length, factor: "0.9144", offset: "0" },
+    Unit { names: &["mi", "mile", "miles"], dim: Dim::Length, factor: "1609.344", offset: "0" },
+    Unit { names: &["mg"], dim: Dim::Mass, factor: "0.000001", offset: "0" },
+    Unit { names: &["g"], dim: Dim::Mass, factor: "0.001", offset: "0" },
+    Unit { names: &["kg"], dim: Dim::Mass, factor: "1", offset: "0" },
+    Unit { names: &["t"], dim: Dim::Mass, factor: "1000", offset: "0" },
+    Unit { names: &["oz"], dim: Dim::Mass, factor: "0.028349523125", offset: "0" },
+    Unit { names: &["lb", "lbs"], dim: Dim::Mass, factor: "0.45359237", offset: "0" },
+    Unit { names: &["ms"], dim: Dim::Time, factor: "0.001", offset: "0" },
+    Unit { names: &["s", "sec"], dim: Dim::Time, factor: "1", offset: "0" },
+    Unit { names: &["min"], dim: Dim::Time, factor: "60", offset: "0" },
+    Unit { names: &["h", "hr"], dim: Dim::Time, factor: "3600", offset: "0" },
+    Unit { names: &["d", "day", "days"], dim: Dim::Time, factor: "86400", offset: "0" },
+    Unit { names: &["ml"], dim: Dim::Volume, factor: "0.001", offset: "0" },
+    Unit { names: &["l"], dim: Dim::Volume, factor: "1", offset: "0" },
+    Unit { names: &["gal"], dim: Dim::Volume, factor: "3.785411784", offset: "0" },
+    Unit { names: &["c", "degc", "°c"], dim: Dim::Temperature, factor: "1", offset: "273.15" },
+    Unit { names: &["f", "degf", "°f"], dim: Dim::Temperature, factor: "0.5555555555555555555555555556", offset: "459.67" },
+    Unit { names: &["k"], dim: Dim::Temperature, factor: "1", offset: "0" },
+];
+
+fn unit(name: &str) -> Option<&'static Unit> {
+    let name = name.to_lowercase();
+    UNITS.iter().find(|u| u.names.contains(&name.as_str()))
+}
+
+fn dec(s: &str) -> Decimal {
+    Decimal::from_str(s).unwrap()
+}
+
+fn overflow() -> String {
+    "arithmetic overflow".to_string()
+}
+
+#[derive(Clone, Copy)]
+struct Quantity {
+    base: Decimal,
+    unit: Option<&'static Unit>,
+}
+
+impl Quantity {
+    fn scalar(value: Decimal) -> Quantity {
+        Quantity { base: value, unit: None }
+    }
+
+    fn with_unit(value: Decimal, unit: &'static Unit) -> Result<Quantity, String> {
+        let base = value.checked_add(dec(unit.offset)).and_then(|v| v.checked_mul(dec(unit.factor))).ok_or_else(overflow)?;
+        Ok(Quantity { base, unit: Some(unit) })
+    }
+
+    fn dim(&self) -> Option<Dim> {
+        self.unit.map(|u| u.dim)
+    }
+
+    fn value_in(&self, unit: &Unit) -> Result<Decimal, String> {
+        self.base.checked_div(dec(unit.factor)).and_then(|v| v.checked_sub(dec(unit.offset))).ok_or_else(overflow)
+    }
+
+    fn display(&self) -> Result<String, String> {
+        let (value, name) = match self.unit {
+            Some(unit) => (self.value_in(unit)?, Some(unit.names[0])),
+            None => (self.base, None),
+        };
+        let value = value.round_dp(OUTPUT_DP).normalize();
+        Ok(match name {
+            Some(name) => format!("{} {}", value, name),
+            None => value.to_string(),
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Number(String),
+    Word(String),
+    Op(char),
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.' || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Number(chars[start..i].iter().filter(|c| **c != '_').collect()));
+        } else if c.is_alphabetic() || c == '°' {
+            while i < chars.len() && (chars[i].is_alphabetic() || chars[i] == '°') {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().collect()));
+        } else if c == '-' && chars.get(i + 1) == Some(&'>') {
+            i += 2;
+            tokens.push(Token::Word("to".to_string()));
+        } else if "+-*/%^()".contains(c) {
+            i += 1;
+            tokens.push(Token::Op(c));
+        } else {
+            return Err(format!("unexpected character: {}", c));
+        }
+    }
+    Ok(tokens)
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Number(s) | Token::Word(s) => write!(f, "\"{}\"", s),
+            Token::Op(c) => write!(f, "\"{}\"", c),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn nested(&mut self, parse: fn(&mut Parser) -> Result<Quantity, String>) -> Result<Quantity, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err("expression nested too deeply".to_string());
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat_op(&mut self, op: char) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expr(&mut self) -> Result<Quantity, String> {
+        let value = self.sum()?;
+        if let Some(Token::Word(word)) = self.peek() {
+            if word == "to" {
+                self.pos += 1;
+                let target = match self.peek() {
+                    Some(Token::Word(name)) => unit(name).ok_or_else(|| format!("unknown unit: {}", name))?,
+                    _ => return Err("expected a unit after \"to\"".to_string()),
+                };
+                self.pos += 1;
+                if value.dim() != Some(target.dim) {
+                    return Err(format!("cannot convert to {}", target.names[0]));
+                }
+                return Ok(Quantity { base: value.base, unit: Some(target) });
+            }
+        }
+        Ok(value)
+    }
+
+    fn sum(&mut self) -> Result<Quantity, String> {
+        let mut left = self.product()?;
+        loop {
+            let subtract = if self.eat_op('+') {
+                false
+            } else if self.eat_op('-') {
+                true
+            } else {
+                return Ok(left);
+            };
+            let right = self.product()?;
+            if left.dim() != right.dim() {
+                return Err("cannot add quantities of different units".to_string());
+            }
+            if left.dim() == Some(Dim::Temperature) {
+                return Err("temperatures can only be converted".to_string());
+            }
+            let base = if subtract { left.base.checked_sub(right.base) } else { left.base.checked_add(right.base) };
+            left.base = base.ok_or_else(overflow)?;
+        }
+    }
+
+    fn product(&mut self) -> Result<Quantity, String> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op(op)) if "*/%".contains(*op) => *op,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.unary()?;
+            if left.dim() == Some(Dim::Temperature) || right.dim() == Some(Dim::Temperature) {
+                return Err("temperatures can only be converted".to_string());
+            }
+            if right.base.is_zero() && op != '*' {
+                return Err("division by zero".to_string());
+            }
+            left = match (op, left.unit, right.unit) {
+                ('*', Some(_), Some(_)) => return Err("cannot multiply two quantities with units".to_string()),
+                ('*', _, _) => Quantity { base: left.base.checked_mul(right.base).ok_or_else(overflow)?, unit: left.unit.or(right.unit) },
+                ('/', Some(l), Some(r)) if l.dim == r.dim => Quantity::scalar(left.base.checked_div(right.base).ok_or_else(overflow)?),
+                ('/', _, None) => Quantity { base: left.base.checked_div(right.base).ok_or_else(overflow)?, unit: left.unit },
+                ('%', _, None) => Quantity { base: left.base.checked_rem(right.base).ok_or_else(overflow)?, unit: left.unit },
+                _ => return Err("unsupported unit arithmetic".to_string()),
+            };
+        }
+    }
+
+    fn power(&mut self) -> Result<Quantity, String> {
+        let base = self.primary()?;
+        if !self.eat_op('^') {
+            return Ok(base);
+        }
+        let exponent = self.nested(Parser::unary)?;
+        if base.unit.is_some() || exponent.unit.is_some() || !exponent.base.is_integer() {
+            return Err("exponents must be integers without units".to_string());
+        }
+        let n = exponent.base.to_i64().filter(|n| n.unsigned_abs() <= MAX_EXPONENT).ok_or("exponent too large")?;
+        let mut result = Decimal::ONE;
+        for _ in 0..n.unsigned_abs() {
+            result = result.checked_mul(base.base).ok_or_else(overflow)?;
+        }
+        if n < 0 {
+            result = Decimal::ONE.checked_div(result).ok_or("division by zero")?;
+        }
+        Ok(Quantity::scalar(result))
+    }
+
+    fn unary(&mut self) -> Result<Quantity, String> {
+        if self.eat_op('-') {
+            let mut value = self.nested(Parser::unary)?;
+            if value.dim() == Some(Dim::Temperature) {
+                let unit = value.unit.unwrap();
+                return Quantity::with_unit(-value.value_in(unit)?, unit);
+            }
+            value.base = -value.base;
+            return Ok(value);
+        }
+        self.power()
+    }
+
+    fn primary(&mut self) -> Result<Quantity, String> {
+        let value = match self.peek().cloned() {
+            Some(Token::Number(number)) => {
+                self.pos += 1;
+                Decimal::from_str(&number).map_err(|_| format!("invalid number: {}", number))?
+            }
+            Some(Token::Op('(')) => {
+                self.pos += 1;
+                let value = self.nested(Parser::sum)?;
+                if !self.eat_op(')') {
+                    return Err("expected )".to_string());
+                }
+                return Ok(value);
+            }
+            Some(token) => return Err(format!("unexpected {}", token)),
+            None => return Err("unexpected end of expression".to_string()),
+        };
+        match self.peek() {
+            Some(Token::Word(word)) if word != "to" => {
+                let unit = unit(word).ok_or_else(|| format!("unknown unit: {}", word))?;
+                self.pos += 1;
+                Quantity::with_unit(value, unit)
+            }
+            _ => Ok(Quantity::scalar(value)),
+        }
+    }
+}
+
+pub fn eval(expr: &str) -> Result<String, String> {
+    let mut parser = Parser { tokens: tokenize(expr)?, pos: 0, depth: 0 };
+    let value = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("unexpected {}", token));
+    }
+    value.display()
+}
+
+fn calc_eval(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let expr = match host::string_arg(scope, &args, 0, "expression") {
+        Some(expr) => expr,
+        None => return,
+    };
+    match eval(&expr) {
+        Ok(result) => host::return_string(scope, &mut rv, &result),
+        Err(e) => host::throw_error(scope, &format!("calc: {}", e)),
+    }
+}
+
+pub fn install(scope: &mut rusty_v8::HandleScope) {
+    let object = rusty_v8::Object::new(scope);
+    host::set_function(scope, object, "eval", calc_eval);
+    host::set_global(scope, "calc", object.into());
+}
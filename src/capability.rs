@@ -0,0 +1,48 @@
+use crate::host;
+use std::convert::TryFrom;
+
+const CAPABILITY_ERROR: &str = r#"Object.defineProperty(globalThis, "CapabilityError", {
+    value: class CapabilityError extends Error {
+        constructor(api) {
+            super(api + " is disabled");
+            this.name = "CapabilityError";
+            this.api = api;
+        }
+    },
+});"#;
+
+fn disabled_getter(
+    scope: &mut rusty_v8::HandleScope,
+    key: rusty_v8::Local<rusty_v8::Name>,
+    _args: rusty_v8::PropertyCallbackArguments,
+    _rv: rusty_v8::ReturnValue,
+) {
+    let global = scope.get_current_context().global(scope);
+    let name = rusty_v8::String::new(scope, "CapabilityError").unwrap();
+    let class = global
+        .get(scope, name.into())
+        .and_then(|class| rusty_v8::Local::<rusty_v8::Function>::try_from(class).ok());
+    let error = class.and_then(|class| class.new_instance(scope, &[key.into()]));
+    match error {
+        Some(error) => {
+            scope.throw_exception(error.into());
+        }
+        None => {
+            let api = key.to_rust_string_lossy(scope);
+            host::throw_error(scope, &format!("{} is disabled", api));
+        }
+    }
+}
+
+pub fn install(scope: &mut rusty_v8::HandleScope) {
+    let source = rusty_v8::String::new(scope, CAPABILITY_ERROR).unwrap();
+    if let Some(script) = rusty_v8::Script::compile(scope, source, None) {
+        script.run(scope);
+    }
+}
+
+pub fn disable(scope: &mut rusty_v8::HandleScope, api: &str) {
+    let global = scope.get_current_context().global(scope);
+    let key = rusty_v8::String::new(scope, api).unwrap();
+    global.set_accessor(scope, key.into(), host::guard_getter(disabled_getter));
+}
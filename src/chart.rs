@@ -0,0 +1,154 @@
+use crate::host;
+use base64::Engine;
+use resvg::{tiny_skia, usvg};
+use serde_json::Value;
+use std::fmt::Write;
+use std::sync::{Arc, OnceLock};
+
+const MAX_SIDE: u32 = 2000;
+const MAX_POINTS: usize = 500;
+const MAX_SVG_BYTES: usize = 256 * 1024;
+const MAX_NODES: usize = 10_000;
+const MAX_DEPTH: usize = 64;
+const REFERENCING_ELEMENTS: &[&str] = &["use", "feImage"];
+const DEFAULT_WIDTH: u32 = 600;
+const DEFAULT_HEIGHT: u32 = 400;
+const MARGIN: f64 = 40.0;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn dimension(spec: &Value, key: &str, default: u32) -> Result<u32, String> {
+    match spec.get(key) {
+        None => Ok(default),
+        Some(v) => match v.as_u64() {
+            Some(n) if n > 0 && n <= MAX_SIDE as u64 => Ok(n as u32),
+            _ => Err(format!("{} must be between 1 and {}", key, MAX_SIDE)),
+        },
+    }
+}
+
+pub fn spec_to_svg(spec: &Value) -> Result<String, String> {
+    let kind = spec["type"].as_str().unwrap_or("bar");
+    let values: Vec<f64> = match spec["values"].as_array() {
+        Some(values) => values.iter().map(|v| v.as_f64().ok_or("values must be numbers")).collect::<Result<_, _>>()?,
+        None => return Err("values must be an array of numbers".to_string()),
+    };
+    if values.is_empty() || values.len() > MAX_POINTS {
+        return Err(format!("values must have between 1 and {} points", MAX_POINTS));
+    }
+    let labels: Vec<String> = spec["labels"].as_array().map_or_else(Vec::new, |l| l.iter().map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string)).collect());
+    let width = dimension(spec, "width", DEFAULT_WIDTH)?;
+    let height = dimension(spec, "height", DEFAULT_HEIGHT)?;
+    let (w, h) = (width as f64, height as f64);
+    let max = values.iter().cloned().fold(0.0_f64, f64::max);
+    let min = values.iter().cloned().fold(0.0_f64, f64::min);
+    let range = if max - min > 0.0 { max - min } else { 1.0 };
+    let plot_w = (w - 2.0 * MARGIN).max(1.0);
+    let plot_h = (h - 2.0 * MARGIN).max(1.0);
+    let y = |v: f64| MARGIN + plot_h * (max - v) / range;
+    let step = plot_w / values.len() as f64;
+    let mut svg = format!(r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}"><rect width="100%" height="100%" fill="white"/>"#, width, height);
+    if let Some(title) = spec["title"].as_str() {
+        let _ = write!(svg, r#"<text x="{}" y="{}" font-size="16" text-anchor="middle">{}</text>"#, w / 2.0, MARGIN / 2.0 + 6.0, escape(title));
+    }
+    let _ = write!(svg, r##"<line x1="{m}" y1="{z}" x2="{r}" y2="{z}" stroke="#888"/>"##, m = MARGIN, z = y(0.0), r = w - MARGIN);
+    match kind {
+        "bar" => {
+            for (i, v) in values.iter().enumerate() {
+                let top = y(v.max(0.0));
+                let _ = write!(svg, r##"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="#5865f2"/>"##, MARGIN + step * i as f64 + step * 0.1, top, step * 0.8, (y(v.min(0.0)) - top).max(0.0));
+            }
+        }
+        "line" => {
+            let points: Vec<String> = values.iter().enumerate().map(|(i, v)| format!("{:.2},{:.2}", MARGIN + step * (i as f64 + 0.5), y(*v))).collect();
+            let _ = write!(svg, r##"<polyline points="{}" fill="none" stroke="#5865f2" stroke-width="2"/>"##, points.join(" "));
+        }
+        _ => return Err("type must be \"bar\" or \"line\"".to_string()),
+    }
+    for (i, label) in labels.iter().take(values.len()).enumerate() {
+        let _ = write!(svg, r#"<text x="{:.2}" y="{:.2}" font-size="12" text-anchor="middle">{}</text>"#, MARGIN + step * (i as f64 + 0.5), h - MARGIN / 2.0, escape(label));
+    }
+    svg.push_str("</svg>");
+    Ok(svg)
+}
+
+fn fonts() -> Arc<usvg::fontdb::Database> {
+    static FONTS: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut db = usvg::fontdb::Database::new();
+            db.load_system_fonts();
+            Arc::new(db)
+        })
+        .clone()
+}
+
+fn count_nodes(group: &usvg::Group) -> usize {
+    group
+        .children()
+        .iter()
+        .map(|node| match node {
+            usvg::Node::Group(group) => 1 + count_nodes(group),
+            _ => 1,
+        })
+        .sum()
+}
+
+fn check_markup(svg: &str) -> Result<(), String> {
+    let doc = usvg::roxmltree::Document::parse(svg).map_err(|e| e.to_string())?;
+    for (i, node) in doc.descendants().filter(|n| n.is_element()).enumerate() {
+        if i >= MAX_NODES {
+            return Err(format!("SVG has more than {} elements", MAX_NODES));
+        }
+        if REFERENCING_ELEMENTS.contains(&node.tag_name().name()) {
+            return Err(format!("<{}> elements are not supported", node.tag_name().name()));
+        }
+        if node.ancestors().take(MAX_DEPTH + 2).count() > MAX_DEPTH + 1 {
+            return Err(format!("SVG nests deeper than {} elements", MAX_DEPTH));
+        }
+    }
+    Ok(())
+}
+
+pub fn render_png(svg: &str) -> Result<Vec<u8>, String> {
+    if svg.len() > MAX_SVG_BYTES {
+        return Err(format!("SVG exceeds {} bytes", MAX_SVG_BYTES));
+    }
+    check_markup(svg)?;
+    let mut options = usvg::Options { fontdb: fonts(), ..usvg::Options::default() };
+    options.image_href_resolver.resolve_string = Box::new(|_: &str, _: &usvg::Options| None);
+    let tree = usvg::Tree::from_str(svg, &options).map_err(|e| e.to_string())?;
+    if count_nodes(tree.root()) > MAX_NODES {
+        return Err(format!("SVG has more than {} elements", MAX_NODES));
+    }
+    let size = tree.size().to_int_size();
+    if size.width() > MAX_SIDE || size.height() > MAX_SIDE {
+        return Err(format!("image must be at most {}x{}", MAX_SIDE, MAX_SIDE));
+    }
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height()).ok_or("invalid image size")?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    pixmap.encode_png().map_err(|e| e.to_string())
+}
+
+fn chart_render(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let svg = if args.get(0).is_string() {
+        Ok(args.get(0).to_rust_string_lossy(scope))
+    } else {
+        match host::to_json(scope, args.get(0)).filter(Value::is_object) {
+            Some(spec) => spec_to_svg(&spec),
+            None => return host::throw_type_error(scope, "chart must be an SVG string or a spec object"),
+        }
+    };
+    match svg.and_then(|svg| render_png(&svg)) {
+        Ok(png) => host::return_string(scope, &mut rv, &base64::engine::general_purpose::STANDARD.encode(png)),
+        Err(e) => host::throw_error(scope, &format!("chart: {}", e)),
+    }
+}
+
+pub fn install(scope: &mut rusty_v8::HandleScope) {
+    let object = rusty_v8::Object::new(scope);
+    host::set_function(scope, object, "render", chart_render);
+    host::set_global(scope, "chart", object.into());
+}
@@ -0,0 +1,47 @@
+use base64::Engine;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde::Deserialize;
+use std::io::Read;
+
+const MAX_RATIO: usize = 100;
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    Gzip,
+    Deflate,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Envelope {
+    pub compression: Compression,
+    pub payload: String,
+}
+
+pub enum DecompressError {
+    Invalid(String),
+    TooLarge,
+}
+
+impl Envelope {
+    pub fn decompress(&self, limit: usize) -> Result<Vec<u8>, DecompressError> {
+        let compressed = base64::engine::general_purpose::STANDARD
+            .decode(&self.payload)
+            .map_err(|e| DecompressError::Invalid(e.to_string()))?;
+        let limit = limit.min(compressed.len().saturating_mul(MAX_RATIO));
+        let reader: Box<dyn Read> = match self.compression {
+            Compression::Gzip => Box::new(GzDecoder::new(&compressed[..])),
+            Compression::Deflate => Box::new(ZlibDecoder::new(&compressed[..])),
+        };
+        let mut out = Vec::new();
+        reader
+            .take(limit as u64 + 1)
+            .read_to_end(&mut out)
+            .map_err(|e| DecompressError::Invalid(e.to_string()))?;
+        if out.len() > limit {
+            return Err(DecompressError::TooLarge);
+        }
+        Ok(out)
+    }
+}
@@ -0,0 +1,299 @@
+use crate::filter::Filter;
+use crate::log::Level;
+use crate::moderation::{CommandModerator, OutputModerator};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const ALLOWED_V8_FLAGS: &[&str] = &[
+    "jitless",
+    "expose-wasm",
+    "opt",
+    "lazy",
+    "single-threaded",
+    "disallow-code-generation-from-strings",
+    "max-old-space-size",
+    "max-semi-space-size",
+    "stack-size",
+];
+
+#[cfg(not(feature = "chart"))]
+pub const HOST_APIS: &[&str] = &["calc", "console", "data", "datasets", "format", "fs", "random", "search"];
+#[cfg(feature = "chart")]
+pub const HOST_APIS: &[&str] = &["calc", "chart", "console", "data", "datasets", "format", "fs", "random", "search"];
+
+const HARDENED_V8_FLAGS: &[&str] = &[
+    "--jitless",
+    "--no-expose-wasm",
+    "--disallow-code-generation-from-strings",
+];
+
+const HARDENED_MAX_HEAP_BYTES: usize = 16 * 1024 * 1024;
+const HARDENED_TIMEOUT: Duration = Duration::from_secs(2);
+const HARDENED_MAX_LOG_LINES: usize = 100;
+const HARDENED_MAX_LOG_BYTES: usize = 16 * 1024;
+const HARDENED_MAX_PARSE_BYTES: usize = 1024 * 1024;
+
+const DEFAULT_BOA_LOOP_LIMIT: u64 = 10_000_000;
+
+const DEFAULT_MAX_LOG_LINES: usize = 1000;
+const DEFAULT_MAX_LOG_BYTES: usize = 64 * 1024;
+const DEFAULT_MAX_RESULT_BYTES: usize = 1024 * 1024;
+const DEFAULT_MAX_SCRIPT_BYTES: usize = 1024 * 1024;
+const DEFAULT_FS_QUOTA_BYTES: usize = 1024 * 1024;
+const DEFAULT_MAX_PARSE_BYTES: usize = 4 * 1024 * 1024;
+const DEFAULT_MODERATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Wrapper {
+    prefix: String,
+    suffix: String,
+}
+
+impl Wrapper {
+    pub fn parse(template: &str) -> Result<Wrapper, String> {
+        match template.split_once("%SCRIPT%") {
+            Some((prefix, suffix)) if !suffix.contains("%SCRIPT%") => Ok(Wrapper {
+                prefix: prefix.to_string(),
+                suffix: suffix.to_string(),
+            }),
+            _ => Err("wrapper template must contain %SCRIPT% exactly once".to_string()),
+        }
+    }
+
+    pub fn apply(&self, script: &str) -> String {
+        format!("{}{}{}", self.prefix, script, self.suffix)
+    }
+
+    pub fn line_offset(&self) -> i32 {
+        -(self.prefix.matches('\n').count() as i32)
+    }
+
+    pub fn column_offset(&self) -> i32 {
+        if self.prefix.contains('\n') {
+            0
+        } else {
+            -(self.prefix.encode_utf16().count() as i32)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EngineKind {
+    V8,
+    Boa,
+}
+
+impl EngineKind {
+    fn parse(s: &str) -> Option<EngineKind> {
+        match s {
+            "v8" if cfg!(feature = "v8") => Some(EngineKind::V8),
+            "boa" if cfg!(feature = "boa") => Some(EngineKind::Boa),
+            _ => None,
+        }
+    }
+}
+
+pub struct Config {
+    pub engine: EngineKind,
+    pub boa_loop_limit: u64,
+    pub v8_flags: Vec<String>,
+    pub hardened: bool,
+    pub max_heap_bytes: Option<usize>,
+    pub soft_heap_bytes: Option<usize>,
+    pub log_level: Option<Level>,
+    pub timeout: Option<Duration>,
+    pub datasets: HashMap<String, PathBuf>,
+    pub max_log_lines: usize,
+    pub max_log_bytes: usize,
+    pub max_result_bytes: usize,
+    pub max_script_bytes: usize,
+    pub disabled_apis: Vec<String>,
+    pub wrapper: Option<Wrapper>,
+    pub strict: bool,
+    pub filters: Vec<Filter>,
+    pub moderator: Option<Box<dyn OutputModerator>>,
+    pub moderation_fail_open: bool,
+    pub fs_quota_bytes: usize,
+    pub max_parse_bytes: usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            engine: EngineKind::V8,
+            boa_loop_limit: DEFAULT_BOA_LOOP_LIMIT,
+            v8_flags: Vec::new(),
+            hardened: false,
+            max_heap_bytes: None,
+            soft_heap_bytes: None,
+            log_level: None,
+            timeout: None,
+            datasets: HashMap::new(),
+            max_log_lines: DEFAULT_MAX_LOG_LINES,
+            max_log_bytes: DEFAULT_MAX_LOG_BYTES,
+            max_result_bytes: DEFAULT_MAX_RESULT_BYTES,
+            max_script_bytes: DEFAULT_MAX_SCRIPT_BYTES,
+            disabled_apis: Vec::new(),
+            wrapper: None,
+            strict: false,
+            filters: Vec::new(),
+            moderator: None,
+            moderation_fail_open: false,
+            fs_quota_bytes: DEFAULT_FS_QUOTA_BYTES,
+            max_parse_bytes: DEFAULT_MAX_PARSE_BYTES,
+        }
+    }
+}
+
+fn parse_count(arg: &str, value: &str) -> Result<usize, String> {
+    value.parse::<usize>().map_err(|_| format!("invalid value for {}: {}", arg, value))
+}
+
+fn parse_mb(arg: &str, value: &str) -> Result<usize, String> {
+    match value.parse::<usize>().ok().filter(|&mb| mb > 0).and_then(|mb| mb.checked_mul(1024 * 1024)) {
+        Some(bytes) => Ok(bytes),
+        None => Err(format!("invalid value for {}: {}", arg, value)),
+    }
+}
+
+fn check_v8_flag(flag: &str) -> Result<String, String> {
+    let body = flag.strip_prefix("--").ok_or_else(|| format!("invalid V8 flag: {}", flag))?;
+    let (name, value) = match body.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (body, None),
+    };
+    let name = name.replace('_', "-");
+    let base = name.strip_prefix("no-").unwrap_or(&name);
+    if !ALLOWED_V8_FLAGS.contains(&base) {
+        return Err(format!("V8 flag not allowed: {}", flag));
+    }
+    match value {
+        Some(v) if v.parse::<u64>().is_err() => Err(format!("invalid value for V8 flag: {}", flag)),
+        Some(v) => Ok(format!("--{}={}", name, v)),
+        None => Ok(format!("--{}", name)),
+    }
+}
+
+impl Config {
+    pub fn api_enabled(&self, api: &str) -> bool {
+        !self.disabled_apis.iter().any(|a| a == api)
+    }
+
+    pub fn enabled_apis(&self) -> Vec<&'static str> {
+        HOST_APIS.iter().copied().filter(|api| self.api_enabled(api)).collect()
+    }
+
+    fn add_v8_flags(&mut self, flags: &str) -> Result<(), String> {
+        for flag in flags.split_whitespace() {
+            self.v8_flags.push(check_v8_flag(flag)?);
+        }
+        Ok(())
+    }
+
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<Config, String> {
+        let mut config = Config::default();
+        let mut moderator = None;
+        let mut moderation_timeout = DEFAULT_MODERATION_TIMEOUT;
+        while let Some(arg) = args.next() {
+            if let Some(flags) = arg.strip_prefix("--v8-flags=") {
+                config.add_v8_flags(flags)?;
+            } else if arg == "--v8-flags" {
+                let flags = args.next().ok_or("--v8-flags requires a value")?;
+                config.add_v8_flags(&flags)?;
+            } else if let Some(engine) = arg.strip_prefix("--engine=") {
+                config.engine = EngineKind::parse(engine).ok_or_else(|| format!("engine not available: {}", engine))?;
+            } else if let Some(n) = arg.strip_prefix("--boa-loop-limit=") {
+                config.boa_loop_limit = n.parse::<u64>().map_err(|_| format!("invalid value for --boa-loop-limit: {}", n))?;
+            } else if arg == "--strict" {
+                config.strict = true;
+            } else if arg == "--hardened" {
+                config.hardened = true;
+            } else if let Some(mb) = arg.strip_prefix("--max-heap-mb=") {
+                config.max_heap_bytes = Some(parse_mb("--max-heap-mb", mb)?);
+            } else if let Some(mb) = arg.strip_prefix("--soft-heap-mb=") {
+                config.soft_heap_bytes = Some(parse_mb("--soft-heap-mb", mb)?);
+            } else if let Some(ms) = arg.strip_prefix("--timeout-ms=") {
+                match ms.parse::<u64>() {
+                    Ok(ms) if ms > 0 => config.timeout = Some(Duration::from_millis(ms)),
+                    _ => return Err(format!("invalid value for --timeout-ms: {}", ms)),
+                }
+            } else if let Some(dataset) = arg.strip_prefix("--dataset=") {
+                match dataset.split_once('=') {
+                    Some((name, path)) if !name.is_empty() && !path.is_empty() => {
+                        config.datasets.insert(name.to_string(), PathBuf::from(path));
+                    }
+                    _ => return Err(format!("invalid value for --dataset: {}", dataset)),
+                }
+            } else if let Some(n) = arg.strip_prefix("--max-log-lines=") {
+                config.max_log_lines = parse_count("--max-log-lines", n)?;
+            } else if let Some(n) = arg.strip_prefix("--max-log-bytes=") {
+                config.max_log_bytes = parse_count("--max-log-bytes", n)?;
+            } else if let Some(n) = arg.strip_prefix("--max-result-bytes=") {
+                config.max_result_bytes = parse_count("--max-result-bytes", n)?;
+            } else if let Some(n) = arg.strip_prefix("--max-script-bytes=") {
+                config.max_script_bytes = parse_count("--max-script-bytes", n)?;
+            } else if let Some(apis) = arg.strip_prefix("--disabled-apis=") {
+                for api in apis.split(',').filter(|a| !a.is_empty()) {
+                    if !HOST_APIS.contains(&api) {
+                        return Err(format!("unknown host API: {}", api));
+                    }
+                    config.disabled_apis.push(api.to_string());
+                }
+            } else if let Some(path) = arg.strip_prefix("--wrapper=") {
+                let template = std::fs::read_to_string(path).map_err(|e| format!("cannot read wrapper {}: {}", path, e))?;
+                config.wrapper = Some(Wrapper::parse(&template)?);
+            } else if let Some(pattern) = arg.strip_prefix("--redact=") {
+                config.filters.push(Filter::redact(pattern)?);
+            } else if let Some(path) = arg.strip_prefix("--blocklist=") {
+                let words = std::fs::read_to_string(path).map_err(|e| format!("cannot read blocklist {}: {}", path, e))?;
+                config.filters.push(Filter::blocklist(&words)?);
+            } else if let Some(n) = arg.strip_prefix("--max-mentions=") {
+                config.filters.push(Filter::MaxMentions(parse_count("--max-mentions", n)?));
+            } else if let Some(command) = arg.strip_prefix("--moderator=") {
+                moderator = Some(command.to_string());
+            } else if let Some(ms) = arg.strip_prefix("--moderation-timeout-ms=") {
+                match ms.parse::<u64>() {
+                    Ok(ms) if ms > 0 => moderation_timeout = Duration::from_millis(ms),
+                    _ => return Err(format!("invalid value for --moderation-timeout-ms: {}", ms)),
+                }
+            } else if arg == "--moderation-fail-open" {
+                config.moderation_fail_open = true;
+            } else if let Some(n) = arg.strip_prefix("--fs-quota-kb=") {
+                config.fs_quota_bytes = parse_count("--fs-quota-kb", n)?.saturating_mul(1024);
+            } else if let Some(n) = arg.strip_prefix("--max-parse-kb=") {
+                config.max_parse_bytes = parse_count("--max-parse-kb", n)?.saturating_mul(1024);
+            } else if let Some(level) = arg.strip_prefix("--log-level=") {
+                config.log_level = Some(Level::parse(level).ok_or_else(|| format!("invalid log level: {}", level))?);
+            } else {
+                return Err(format!("unknown argument: {}", arg));
+            }
+        }
+        if let Some(command) = moderator {
+            config.moderator = Some(Box::new(CommandModerator::parse(&command, moderation_timeout)?));
+        }
+        if config.hardened {
+            config.v8_flags.extend(HARDENED_V8_FLAGS.iter().map(|f| f.to_string()));
+            let max = config.max_heap_bytes.unwrap_or(HARDENED_MAX_HEAP_BYTES);
+            config.max_heap_bytes = Some(max.min(HARDENED_MAX_HEAP_BYTES));
+            let timeout = config.timeout.unwrap_or(HARDENED_TIMEOUT);
+            config.timeout = Some(timeout.min(HARDENED_TIMEOUT));
+            config.max_log_lines = config.max_log_lines.min(HARDENED_MAX_LOG_LINES);
+            config.max_log_bytes = config.max_log_bytes.min(HARDENED_MAX_LOG_BYTES);
+            config.max_parse_bytes = config.max_parse_bytes.min(HARDENED_MAX_PARSE_BYTES);
+        }
+        if config.strict {
+            let wrapper = config.wrapper.take().unwrap_or_else(|| Wrapper::parse("%SCRIPT%").unwrap());
+            config.wrapper = Some(Wrapper {
+                prefix: format!("\"use strict\";{}", wrapper.prefix),
+                suffix: wrapper.suffix,
+            });
+        }
+        if let Some(soft) = config.soft_heap_bytes {
+            match config.max_heap_bytes {
+                Some(max) if soft < max => {}
+                _ => return Err("--soft-heap-mb must be below --max-heap-mb".to_string()),
+            }
+        }
+        Ok(config)
+    }
+}
@@ -0,0 +1,97 @@
+use crate::host;
+use crate::protocol::LogEntry;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Instant;
+
+const LEVELS: &[&str] = &["log", "info", "warn", "error", "debug"];
+
+pub struct Console {
+    max_lines: usize,
+    max_bytes: usize,
+    bytes: usize,
+    start: Instant,
+    pub output: Vec<LogEntry>,
+    pub truncated: bool,
+}
+
+type State = Rc<RefCell<Console>>;
+
+impl Console {
+    pub fn new(max_lines: usize, max_bytes: usize, start: Instant) -> Console {
+        Console {
+            max_lines,
+            max_bytes,
+            bytes: 0,
+            start,
+            output: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    fn push(&mut self, level: &'static str, text: String) {
+        if self.output.len() >= self.max_lines || self.bytes + text.len() > self.max_bytes {
+            self.truncated = true;
+            return;
+        }
+        self.bytes += text.len();
+        self.output.push(LogEntry {
+            level,
+            text,
+            t_ms: self.start.elapsed().as_millis() as u64,
+        });
+    }
+}
+
+fn format_arg(scope: &mut rusty_v8::HandleScope, value: rusty_v8::Local<rusty_v8::Value>) -> String {
+    let scope = &mut rusty_v8::TryCatch::new(scope);
+    if value.is_object() && !value.is_function() && !value.is_native_error() {
+        if let Some(json) = rusty_v8::json::stringify(scope, value) {
+            return json.to_rust_string_lossy(scope);
+        }
+    }
+    value.to_rust_string_lossy(scope)
+}
+
+fn console_log(
+    scope: &mut rusty_v8::HandleScope,
+    args: rusty_v8::FunctionCallbackArguments,
+    _rv: rusty_v8::ReturnValue,
+) {
+    let state = scope.get_slot::<State>().unwrap().clone();
+    if state.borrow().truncated {
+        return;
+    }
+    let level = args
+        .data()
+        .and_then(|data| data.uint32_value(scope))
+        .and_then(|i| LEVELS.get(i as usize))
+        .unwrap_or(&LEVELS[0]);
+    let text = (0..args.length())
+        .map(|i| format_arg(scope, args.get(i)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    state.borrow_mut().push(level, text);
+}
+
+pub fn install(scope: &mut rusty_v8::HandleScope, console: Console) {
+    let object = rusty_v8::Object::new(scope);
+    for (i, name) in LEVELS.iter().enumerate() {
+        let key = rusty_v8::String::new(scope, name).unwrap();
+        let level = rusty_v8::Integer::new_from_unsigned(scope, i as u32);
+        let function = rusty_v8::Function::builder(host::guard(console_log))
+            .data(level.into())
+            .build(scope)
+            .unwrap();
+        object.set(scope, key.into(), function.into());
+    }
+    host::set_global(scope, "console", object.into());
+    scope.set_slot::<State>(Rc::new(RefCell::new(console)));
+}
+
+pub fn take(isolate: &mut rusty_v8::Isolate) -> Option<Console> {
+    isolate
+        .remove_slot::<State>()
+        .and_then(|state| Rc::try_unwrap(state).ok())
+        .map(RefCell::into_inner)
+}
@@ -0,0 +1,98 @@
+use crate::host;
+use crate::vfs;
+use std::convert::TryFrom;
+use std::time::Instant;
+
+const QUOTA: &str = r#"(ctx, getters) => {
+    const quota = {};
+    for (const key of Object.keys(getters)) {
+        Object.defineProperty(quota, key, { get: getters[key], enumerable: true });
+    }
+    ctx.quota = quota;
+}"#;
+
+pub struct Quota {
+    pub deadline: Option<Instant>,
+    pub max_heap_bytes: Option<usize>,
+}
+
+fn set_number(scope: &mut rusty_v8::HandleScope, rv: &mut rusty_v8::ReturnValue, value: Option<f64>) {
+    match value {
+        Some(value) => rv.set(rusty_v8::Number::new(scope, value).into()),
+        None => rv.set(rusty_v8::null(scope).into()),
+    }
+}
+
+fn quota_time_ms(scope: &mut rusty_v8::HandleScope, _args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let deadline = scope.get_slot::<Quota>().and_then(|quota| quota.deadline);
+    let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()).as_millis() as f64);
+    set_number(scope, &mut rv, remaining);
+}
+
+fn quota_heap_bytes(scope: &mut rusty_v8::HandleScope, _args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let remaining = scope.get_slot::<Quota>().and_then(|quota| quota.max_heap_bytes).map(|max| {
+        let mut heap = rusty_v8::HeapStatistics::default();
+        scope.get_heap_statistics(&mut heap);
+        max.saturating_sub(heap.used_heap_size()) as f64
+    });
+    set_number(scope, &mut rv, remaining);
+}
+
+fn quota_fs_bytes(scope: &mut rusty_v8::HandleScope, _args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let remaining = vfs::remaining(scope).map(|bytes| bytes as f64);
+    set_number(scope, &mut rv, remaining);
+}
+
+fn install_quota(scope: &mut rusty_v8::HandleScope, ctx: rusty_v8::Local<rusty_v8::Value>, quota: Quota) -> Option<()> {
+    let getters = rusty_v8::Object::new(scope);
+    host::set_function(scope, getters, "timeMs", quota_time_ms);
+    host::set_function(scope, getters, "heapBytes", quota_heap_bytes);
+    host::set_function(scope, getters, "fsBytes", quota_fs_bytes);
+    let source = rusty_v8::String::new(scope, QUOTA).unwrap();
+    let define = rusty_v8::Script::compile(scope, source, None)?.run(scope)?;
+    let define = rusty_v8::Local::<rusty_v8::Function>::try_from(define).ok()?;
+    let recv = rusty_v8::undefined(scope).into();
+    define.call(scope, recv, &[ctx, getters.into()])?;
+    scope.set_slot(quota);
+    Some(())
+}
+
+fn freeze(scope: &mut rusty_v8::HandleScope, freeze_fn: rusty_v8::Local<rusty_v8::Function>, value: rusty_v8::Local<rusty_v8::Value>) {
+    let object = match rusty_v8::Local::<rusty_v8::Object>::try_from(value) {
+        Ok(object) => object,
+        Err(_) => return,
+    };
+    if let Some(names) = object.get_own_property_names(scope) {
+        for i in 0..names.length() {
+            let child = names.get_index(scope, i).and_then(|key| object.get(scope, key));
+            if let Some(child) = child {
+                freeze(scope, freeze_fn, child);
+            }
+        }
+    }
+    let undefined = rusty_v8::undefined(scope).into();
+    freeze_fn.call(scope, undefined, &[value]);
+}
+
+fn freeze_function<'s>(scope: &mut rusty_v8::HandleScope<'s>) -> Option<rusty_v8::Local<'s, rusty_v8::Function>> {
+    let global = scope.get_current_context().global(scope);
+    let key = rusty_v8::String::new(scope, "Object").unwrap();
+    let object = global.get(scope, key.into())?;
+    let object = rusty_v8::Local::<rusty_v8::Object>::try_from(object).ok()?;
+    let key = rusty_v8::String::new(scope, "freeze").unwrap();
+    let freeze = object.get(scope, key.into())?;
+    rusty_v8::Local::<rusty_v8::Function>::try_from(freeze).ok()
+}
+
+pub fn install(scope: &mut rusty_v8::HandleScope, value: &serde_json::Value, quota: Quota) {
+    let text = rusty_v8::String::new(scope, &value.to_string()).unwrap();
+    let ctx = match rusty_v8::json::parse(scope, text) {
+        Some(ctx) => ctx,
+        None => return,
+    };
+    install_quota(scope, ctx, quota);
+    if let Some(freeze_fn) = freeze_function(scope) {
+        freeze(scope, freeze_fn, ctx);
+    }
+    host::set_global(scope, "ctx", ctx);
+}
@@ -0,0 +1,88 @@
+use crate::host;
+use serde_json::{Map, Value};
+
+struct Limit(usize);
+
+fn parse_csv(text: &str, options: &Value) -> Result<Value, String> {
+    let header = options["header"].as_bool().unwrap_or(false);
+    let delimiter = match options["delimiter"].as_str() {
+        None => b',',
+        Some(d) if d.len() == 1 => d.as_bytes()[0],
+        Some(d) => return Err(format!("invalid delimiter: {:?}", d)),
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(header)
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let headers = if header {
+        Some(reader.headers().map_err(|e| e.to_string())?.clone())
+    } else {
+        None
+    };
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let row = match &headers {
+            Some(headers) => Value::Object(
+                headers
+                    .iter()
+                    .zip(record.iter())
+                    .map(|(k, v)| (k.to_string(), Value::String(v.to_string())))
+                    .collect::<Map<_, _>>(),
+            ),
+            None => Value::Array(record.iter().map(|v| Value::String(v.to_string())).collect()),
+        };
+        rows.push(row);
+    }
+    Ok(Value::Array(rows))
+}
+
+fn parse_yaml(text: &str, _options: &Value) -> Result<Value, String> {
+    serde_yaml::from_str(text).map_err(|e| e.to_string())
+}
+
+fn parse(
+    scope: &mut rusty_v8::HandleScope,
+    args: rusty_v8::FunctionCallbackArguments,
+    mut rv: rusty_v8::ReturnValue,
+    what: &str,
+    parser: fn(&str, &Value) -> Result<Value, String>,
+) {
+    let text = match host::string_arg(scope, &args, 0, "text") {
+        Some(text) => text,
+        None => return,
+    };
+    let options = match host::options_arg(scope, &args, 1) {
+        Some(options) => options,
+        None => return,
+    };
+    let limit = scope.get_slot::<Limit>().map_or(usize::MAX, |l| l.0);
+    if text.len() > limit {
+        host::throw_error(scope, &format!("{} input exceeds {} bytes", what, limit));
+        return;
+    }
+    match parser(&text, &options) {
+        Ok(value) => match host::from_json(scope, &value) {
+            Some(value) => rv.set(value),
+            None => host::throw_error(scope, &format!("{} result too large", what)),
+        },
+        Err(e) => host::throw_error(scope, &format!("{} parse error: {}", what, e)),
+    }
+}
+
+fn data_parse_csv(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, rv: rusty_v8::ReturnValue) {
+    parse(scope, args, rv, "CSV", parse_csv);
+}
+
+fn data_parse_yaml(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, rv: rusty_v8::ReturnValue) {
+    parse(scope, args, rv, "YAML", parse_yaml);
+}
+
+pub fn install(scope: &mut rusty_v8::HandleScope, max_bytes: usize) {
+    let object = rusty_v8::Object::new(scope);
+    host::set_function(scope, object, "parseCSV", data_parse_csv);
+    host::set_function(scope, object, "parseYAML", data_parse_yaml);
+    host::set_global(scope, "data", object.into());
+    scope.set_slot(Limit(max_bytes));
+}
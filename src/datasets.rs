@@ -0,0 +1,140 @@
+use crate::host;
+use crate::protocol::{ErrorCode, ExecError};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+const MAX_ACCESS_PATHS: usize = 256;
+
+const TRACK: &str = r#"(value, name, record) => {
+    const own = Object.prototype.hasOwnProperty;
+    const wrap = (value, path) => {
+        if (value === null || typeof value !== "object") return value;
+        const children = new Map();
+        return new Proxy(value, {
+            get(target, key, receiver) {
+                const child = Reflect.get(target, key, receiver);
+                if (typeof key !== "string" || !own.call(target, key) || (Array.isArray(target) && key === "length")) return child;
+                const childPath = path + (/^\d+$/.test(key) ? "[]" : "." + key);
+                record(childPath);
+                const cached = children.get(key);
+                if (cached && cached.raw === child) return cached.proxy;
+                const proxy = wrap(child, childPath);
+                children.set(key, { raw: child, proxy });
+                return proxy;
+            },
+        });
+    };
+    return wrap(value, name);
+}"#;
+
+#[derive(Default)]
+pub struct Datasets {
+    paths: HashMap<String, PathBuf>,
+    loaded: HashMap<String, rusty_v8::Global<rusty_v8::Value>>,
+    pub accessed: Vec<String>,
+    pub access_paths: BTreeSet<String>,
+    pub access_paths_truncated: bool,
+}
+
+type State = Rc<RefCell<Datasets>>;
+
+impl Datasets {
+    pub fn resolve(configured: &HashMap<String, PathBuf>, requested: &[String]) -> Result<Datasets, ExecError> {
+        let mut datasets = Datasets::default();
+        for name in requested {
+            match configured.get(name) {
+                Some(path) => {
+                    datasets.paths.insert(name.clone(), path.clone());
+                }
+                None => return Err(ExecError::new(ErrorCode::UnknownDataset, format!("Unknown dataset: {}", name))),
+            }
+        }
+        Ok(datasets)
+    }
+}
+
+fn dataset_record(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, _rv: rusty_v8::ReturnValue) {
+    let path = args.get(0).to_rust_string_lossy(scope);
+    let state = scope.get_slot::<State>().unwrap().clone();
+    let mut state = state.borrow_mut();
+    if state.access_paths.len() < MAX_ACCESS_PATHS || state.access_paths.contains(&path) {
+        state.access_paths.insert(path);
+    } else {
+        state.access_paths_truncated = true;
+    }
+}
+
+fn track<'s>(scope: &mut rusty_v8::HandleScope<'s>, value: rusty_v8::Local<'s, rusty_v8::Value>, name: &str) -> Option<rusty_v8::Local<'s, rusty_v8::Value>> {
+    let source = rusty_v8::String::new(scope, TRACK).unwrap();
+    let factory = rusty_v8::Script::compile(scope, source, None)?.run(scope)?;
+    let factory = rusty_v8::Local::<rusty_v8::Function>::try_from(factory).ok()?;
+    let name = rusty_v8::String::new(scope, name)?.into();
+    let record = rusty_v8::Function::new(scope, host::guard(dataset_record))?.into();
+    let recv = rusty_v8::undefined(scope).into();
+    factory.call(scope, recv, &[value, name, record])
+}
+
+fn dataset_getter(
+    scope: &mut rusty_v8::HandleScope,
+    key: rusty_v8::Local<rusty_v8::Name>,
+    _args: rusty_v8::PropertyCallbackArguments,
+    mut rv: rusty_v8::ReturnValue,
+) {
+    let state = scope.get_slot::<State>().unwrap().clone();
+    let name = key.to_rust_string_lossy(scope);
+    if let Some(value) = state.borrow().loaded.get(&name) {
+        let value = rusty_v8::Local::new(scope, value);
+        rv.set(value);
+        return;
+    }
+    let path = match state.borrow().paths.get(&name) {
+        Some(path) => path.clone(),
+        None => return,
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            error!("failed to read dataset {} from {}: {}", name, path.display(), e);
+            host::throw_error(scope, &format!("Dataset unavailable: {}", name));
+            return;
+        }
+    };
+    let text = match rusty_v8::String::new(scope, &text) {
+        Some(text) => text,
+        None => {
+            host::throw_error(scope, &format!("Dataset unavailable: {}", name));
+            return;
+        }
+    };
+    if let Some(value) = rusty_v8::json::parse(scope, text).and_then(|value| track(scope, value, &name)) {
+        let global = rusty_v8::Global::new(scope, value);
+        let mut state = state.borrow_mut();
+        state.loaded.insert(name.clone(), global);
+        state.accessed.push(name);
+        rv.set(value);
+    }
+}
+
+pub fn install(scope: &mut rusty_v8::HandleScope, datasets: Datasets) {
+    if datasets.paths.is_empty() {
+        return;
+    }
+    let object = rusty_v8::Object::new(scope);
+    for name in datasets.paths.keys() {
+        let key = rusty_v8::String::new(scope, name).unwrap();
+        object.set_accessor(scope, key.into(), host::guard_getter(dataset_getter));
+    }
+    host::set_global(scope, "datasets", object.into());
+    scope.set_slot::<State>(Rc::new(RefCell::new(datasets)));
+}
+
+pub fn take(isolate: &mut rusty_v8::Isolate) -> Datasets {
+    isolate
+        .remove_slot::<State>()
+        .and_then(|state| Rc::try_unwrap(state).ok())
+        .map(RefCell::into_inner)
+        .unwrap_or_default()
+}
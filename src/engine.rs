@@ -0,0 +1,213 @@
+use crate::config::{Config, EngineKind};
+use crate::{filter, log, messages, moderation};
+use crate::protocol::{ErrorCode, ExecError, Input, ResultFormat, ScriptResult};
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const HANDLERS: &[&str] = &["main", "handler"];
+
+#[derive(Default)]
+pub struct Cancel {
+    terminate: Mutex<Option<Box<dyn Fn() + Send>>>,
+    cancelled: AtomicBool,
+}
+
+impl Cancel {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        if let Some(terminate) = &*self.terminate.lock().unwrap() {
+            terminate();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn attach(&self, terminate: Box<dyn Fn() + Send>) {
+        *self.terminate.lock().unwrap() = Some(terminate);
+        if self.is_cancelled() {
+            self.cancel();
+        }
+    }
+
+    pub fn detach(&self) {
+        self.terminate.lock().unwrap().take();
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Capabilities {
+    pub calc: bool,
+    pub chart: bool,
+    pub console: bool,
+    pub ctx: bool,
+    pub data: bool,
+    pub datasets: bool,
+    pub format: bool,
+    pub random: bool,
+    pub search: bool,
+    pub extended_results: bool,
+    pub alloc_profile: bool,
+    pub host_trace: bool,
+    pub fs: bool,
+    pub heap_limit: bool,
+    pub timeout: bool,
+}
+
+pub trait Engine: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn capabilities(&self) -> Capabilities;
+    fn exec(&self, input: &Input, config: &Config, cancel: Option<&Cancel>) -> ScriptResult;
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn references(script: &str, global: &str) -> bool {
+    script.match_indices(global).any(|(i, _)| {
+        let before = script[..i].chars().next_back();
+        let after = script[i + global.len()..].trim_start().chars().next();
+        !before.is_some_and(|c| is_ident(c) || c == '.') && matches!(after, Some('.') | Some('['))
+    })
+}
+
+// A script's own `const data = …` shadows the host API of the same name.
+fn declares(script: &str, name: &str) -> bool {
+    script.match_indices(name).any(|(i, _)| {
+        let before = script[..i].trim_end();
+        let after = script[i + name.len()..].chars().next();
+        before.len() < i
+            && !after.is_some_and(is_ident)
+            && ["const", "let", "var"].iter().any(|kw| {
+                before.ends_with(kw) && !before[..before.len() - kw.len()].chars().next_back().is_some_and(is_ident)
+            })
+    })
+}
+
+fn check(engine: &dyn Engine, input: &Input, config: &Config) -> Result<(), ExecError> {
+    let caps = engine.capabilities();
+    let apis = [
+        ("calc", caps.calc),
+        ("chart", caps.chart),
+        ("console", caps.console),
+        ("data", caps.data),
+        ("format", caps.format),
+        ("fs", caps.fs),
+        ("random", caps.random),
+        ("search", caps.search),
+    ];
+    let unsupported_api = apis
+        .iter()
+        .find(|(api, supported)| {
+            !supported && config.api_enabled(api) && references(&input.script, api) && !declares(&input.script, api)
+        })
+        .map(|(api, _)| *api);
+    let missing = if unsupported_api.is_some() {
+        unsupported_api
+    } else if !caps.ctx && references(&input.script, "ctx") {
+        Some("ctx")
+    } else if !input.datasets.is_empty() && !caps.datasets {
+        Some("datasets")
+    } else if input.result_format == ResultFormat::Extended && !caps.extended_results {
+        Some("result_format")
+    } else if input.profile_alloc && !caps.alloc_profile {
+        Some("profile_alloc")
+    } else if input.trace_host_calls && !caps.host_trace {
+        Some("trace_host_calls")
+    } else if input.return_files && !caps.fs {
+        Some("return_files")
+    } else if config.max_heap_bytes.is_some() && !caps.heap_limit {
+        Some("--max-heap-mb")
+    } else if config.timeout.is_some() && !caps.timeout {
+        Some("--timeout-ms")
+    } else if input.deadline_unix_ms.is_some() && !caps.timeout {
+        Some("deadline_unix_ms")
+    } else {
+        None
+    };
+    match missing {
+        Some(feature) => Err(ExecError::new(ErrorCode::Unsupported, format!("Unsupported: {} ({})", feature, engine.name()))),
+        None => Ok(()),
+    }
+}
+
+fn required_permissions(script: &str) -> Vec<&str> {
+    script
+        .lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with("//"))
+        .filter_map(|line| line.trim_start_matches('/').trim().strip_prefix("@requires "))
+        .flat_map(|list| list.split(',').map(str::trim).filter(|p| !p.is_empty()))
+        .collect()
+}
+
+fn check_permissions(input: &Input) -> Result<(), ExecError> {
+    match required_permissions(&input.script).into_iter().find(|p| !input.permissions.iter().any(|g| g == p)) {
+        Some(missing) => Err(ExecError::new(ErrorCode::PermissionDenied, format!("Permission denied: {}", missing))),
+        None => Ok(()),
+    }
+}
+
+pub fn select(kind: EngineKind) -> Option<Box<dyn Engine>> {
+    match kind {
+        #[cfg(feature = "v8")]
+        EngineKind::V8 => Some(Box::new(crate::V8Engine)),
+        #[cfg(feature = "boa")]
+        EngineKind::Boa => Some(Box::new(crate::boa::BoaEngine)),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+pub fn run(engine: &dyn Engine, input: &Input, config: &Config, cancel: Option<&Cancel>) -> ScriptResult {
+    let mut res = log::with_request_id(input.request_id.as_deref(), || {
+        debug!("running on {}", engine.name());
+        match check(engine, input, config).and_then(|()| check_permissions(input)) {
+            Ok(()) => engine.exec(input, config, cancel),
+            Err(e) => ScriptResult::from(e),
+        }
+    });
+    if res.error_kind.is_some() {
+        res.error = messages::localize(input.lang, &res.error);
+    }
+    filter::apply(&config.filters, &mut res);
+    res.request_id = input.request_id.clone();
+    if let Some(moderator) = &config.moderator {
+        moderation::apply(&**moderator, config.moderation_fail_open, &mut res);
+    }
+    res
+}
+
+pub fn elapsed_ms(since: Instant) -> f64 {
+    since.elapsed().as_secs_f64() * 1000.0
+}
+
+pub fn unknown_entry(entry: &str) -> ExecError {
+    ExecError::new(ErrorCode::UnknownEntry, format!("Unknown entry: {}", entry))
+}
+
+pub fn calls_result(results: Vec<Value>, max_result_bytes: usize) -> Result<Value, ExecError> {
+    let results = Value::Array(results);
+    if results.to_string().len() > max_result_bytes {
+        return Err(ExecError::new(ErrorCode::ResultTooLarge, "Result too large"));
+    }
+    Ok(results)
+}
+
+pub fn effective_timeout(timeout: Option<Duration>, deadline_unix_ms: Option<u64>) -> Result<Option<Duration>, ExecError> {
+    let deadline = match deadline_unix_ms.and_then(|ms| UNIX_EPOCH.checked_add(Duration::from_millis(ms))) {
+        Some(deadline) => deadline,
+        None => return Ok(timeout),
+    };
+    match deadline.duration_since(SystemTime::now()) {
+        Ok(remaining) if !remaining.is_zero() => {
+            debug!("{} ms left until the deadline", remaining.as_millis());
+            Ok(Some(timeout.map_or(remaining, |t| t.min(remaining))))
+        }
+        _ => Err(ExecError::new(ErrorCode::Deadline, "Deadline exceeded")),
+    }
+}
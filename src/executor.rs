@@ -0,0 +1,84 @@
+use crate::config::Config;
+use crate::engine::{self, Engine};
+use crate::protocol::{self, ErrorCode, Input, ScriptResult};
+use crate::Cancel;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Instant;
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+pub struct Executor {
+    engine: Arc<dyn Engine>,
+    config: Arc<Config>,
+    jobs: Option<mpsc::Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+fn lost() -> ScriptResult {
+    ScriptResult::err(ErrorCode::Internal, "Internal error")
+}
+
+impl Executor {
+    pub fn new(threads: usize, config: Config) -> Result<Executor, String> {
+        let engine: Arc<dyn Engine> = engine::select(config.engine).ok_or_else(|| format!("engine not available: {:?}", config.engine))?.into();
+        crate::initialize(&config.v8_flags)?;
+        let (jobs, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        let workers = (0..threads.max(1))
+            .map(|i| {
+                let rx = rx.clone();
+                std::thread::Builder::new()
+                    .name(format!("script-worker-{}", i))
+                    .spawn(move || loop {
+                        let job = rx.lock().unwrap().recv();
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => break,
+                        }
+                    })
+                    .map_err(|e| e.to_string())
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Executor {
+            engine,
+            config: Arc::new(config),
+            jobs: Some(jobs),
+            workers,
+        })
+    }
+
+    pub async fn run(&self, mut input: Input, token: CancellationToken) -> ScriptResult {
+        input.queued_at = Some(Instant::now());
+        let (tx, mut rx) = oneshot::channel();
+        let cancel = Arc::new(Cancel::default());
+        let job_cancel = cancel.clone();
+        let engine = self.engine.clone();
+        let config = self.config.clone();
+        let job: Job = Box::new(move || {
+            let res = protocol::contain(|| engine::run(&*engine, &input, &config, Some(&job_cancel)));
+            let _ = tx.send(res.unwrap_or_else(ScriptResult::from));
+        });
+        if self.jobs.as_ref().is_none_or(|jobs| jobs.send(job).is_err()) {
+            return lost();
+        }
+        tokio::select! {
+            res = &mut rx => res.unwrap_or_else(|_| lost()),
+            _ = token.cancelled() => {
+                cancel.cancel();
+                rx.await.unwrap_or_else(|_| lost())
+            }
+        }
+    }
+}
+
+impl Drop for Executor {
+    fn drop(&mut self) {
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::protocol::ScriptResult;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+const REDACTED: &str = "[redacted]";
+
+pub enum Filter {
+    Redact(Regex),
+    Blocklist(Regex),
+    MaxMentions(usize),
+}
+
+impl Filter {
+    pub fn redact(pattern: &str) -> Result<Filter, String> {
+        Regex::new(pattern).map(Filter::Redact).map_err(|e| format!("invalid value for --redact: {}", e))
+    }
+
+    pub fn blocklist(words: &str) -> Result<Filter, String> {
+        let words: Vec<_> = words.lines().map(str::trim).filter(|w| !w.is_empty()).map(regex::escape).collect();
+        if words.is_empty() {
+            return Err("blocklist is empty".to_string());
+        }
+        let pattern = format!(r"(?i)\b(?:{})\b", words.join("|"));
+        Regex::new(&pattern).map(Filter::Blocklist).map_err(|e| format!("invalid blocklist: {}", e))
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Filter::Redact(_) => "redact",
+            Filter::Blocklist(_) => "blocklist",
+            Filter::MaxMentions(_) => "mentions",
+        }
+    }
+}
+
+fn mention() -> &'static Regex {
+    static MENTION: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+    MENTION.get_or_init(|| Regex::new(r"<@[!&]?\d+>|@everyone|@here").unwrap())
+}
+
+struct Pipeline<'a> {
+    filters: &'a [Filter],
+    mentions: usize,
+    hits: BTreeMap<&'static str, usize>,
+}
+
+impl Pipeline<'_> {
+    fn text(&mut self, text: &mut String) {
+        for filter in self.filters {
+            let mut hits = 0;
+            let replaced = match filter {
+                Filter::Redact(re) => re.replace_all(text, |_: &regex::Captures| {
+                    hits += 1;
+                    REDACTED.to_string()
+                }),
+                Filter::Blocklist(re) => re.replace_all(text, |c: &regex::Captures| {
+                    hits += 1;
+                    "*".repeat(c[0].chars().count())
+                }),
+                Filter::MaxMentions(max) => {
+                    let seen = &mut self.mentions;
+                    mention().replace_all(text, |c: &regex::Captures| {
+                        *seen += 1;
+                        if *seen > *max {
+                            hits += 1;
+                            c[0].replacen('@', "@\u{200b}", 1)
+                        } else {
+                            c[0].to_string()
+                        }
+                    })
+                }
+            };
+            if hits > 0 {
+                *text = replaced.into_owned();
+                *self.hits.entry(filter.name()).or_default() += hits;
+            }
+        }
+    }
+
+    fn value(&mut self, value: &mut Value) {
+        match value {
+            Value::String(s) => self.text(s),
+            Value::Array(items) => items.iter_mut().for_each(|v| self.value(v)),
+            Value::Object(map) => map.values_mut().for_each(|v| self.value(v)),
+            _ => {}
+        }
+    }
+}
+
+pub fn apply(filters: &[Filter], res: &mut ScriptResult) {
+    if filters.is_empty() {
+        return;
+    }
+    let mut pipeline = Pipeline { filters, mentions: 0, hits: BTreeMap::new() };
+    pipeline.value(&mut res.result);
+    pipeline.text(&mut res.error);
+    if let Some(detail) = &mut res.error_detail {
+        pipeline.value(detail);
+    }
+    for entry in &mut res.logs {
+        pipeline.text(&mut entry.text);
+    }
+    for data in res.files.iter_mut().flat_map(|files| files.values_mut()) {
+        pipeline.text(data);
+    }
+    if let Some(random) = &mut res.stats.random {
+        pipeline.value(random);
+    }
+    if let Some(calls) = &mut res.host_calls {
+        pipeline.value(calls);
+    }
+    res.stats.filter_hits = pipeline.hits;
+}
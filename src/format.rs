@@ -0,0 +1,259 @@
+use crate::host;
+use crate::markdown::{self, Platform};
+use serde_json::Value;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const MARKDOWN_SPECIAL: &[char] = &['\\', '*', '_', '~', '`', '|', '>', '[', ']', '(', ')', '#'];
+
+pub fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if MARKDOWN_SPECIAL.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+pub fn codeblock(text: &str, lang: &str) -> String {
+    let lang: String = lang
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '_' | '#'))
+        .collect();
+    let body = text.replace("```", "`\u{200b}``");
+    let newline = if body.ends_with('\n') { "" } else { "\n" };
+    format!("```{}\n{}{}```", lang, body, newline)
+}
+
+#[derive(Clone, Copy)]
+pub enum Unit {
+    Graphemes,
+    Utf16,
+    Bytes,
+}
+
+impl Unit {
+    fn parse(s: &str) -> Option<Unit> {
+        match s {
+            "graphemes" => Some(Unit::Graphemes),
+            "utf16" => Some(Unit::Utf16),
+            "bytes" => Some(Unit::Bytes),
+            _ => None,
+        }
+    }
+
+    fn len(self, s: &str) -> usize {
+        match self {
+            Unit::Graphemes => s.graphemes(true).count(),
+            Unit::Utf16 => s.encode_utf16().count(),
+            Unit::Bytes => s.len(),
+        }
+    }
+}
+
+pub fn truncate(text: &str, max: usize, unit: Unit, ellipsis: &str) -> String {
+    if unit.len(text) <= max {
+        return text.to_string();
+    }
+    let budget = max.saturating_sub(unit.len(ellipsis));
+    let mut used = 0;
+    let mut out = String::new();
+    for g in text.graphemes(true) {
+        used += unit.len(g);
+        if used > budget {
+            break;
+        }
+        out.push_str(g);
+    }
+    if unit.len(ellipsis) <= max {
+        out.push_str(ellipsis);
+    }
+    out
+}
+
+pub fn normalize(text: &str, form: &str) -> Option<String> {
+    match form {
+        "NFC" => Some(text.nfc().collect()),
+        "NFD" => Some(text.nfd().collect()),
+        "NFKC" => Some(text.nfkc().collect()),
+        "NFKD" => Some(text.nfkd().collect()),
+        _ => None,
+    }
+}
+
+fn cell(value: &Value) -> String {
+    let text = match value {
+        Value::Null => "".to_string(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    };
+    text.replace(['\r', '\n'], " ")
+}
+
+pub fn table(rows: &Value, header: Option<&Value>) -> Result<String, String> {
+    let rows = rows.as_array().ok_or("rows must be an array")?;
+    let mut columns: Vec<String> = match header {
+        Some(Value::Array(names)) => names.iter().map(cell).collect(),
+        Some(_) => return Err("header must be an array".to_string()),
+        None => Vec::new(),
+    };
+    if header.is_none() {
+        for row in rows {
+            if let Value::Object(map) = row {
+                for key in map.keys() {
+                    if !columns.contains(key) {
+                        columns.push(key.clone());
+                    }
+                }
+            }
+        }
+    }
+    let mut body = Vec::with_capacity(rows.len());
+    for row in rows {
+        body.push(match row {
+            Value::Array(cells) => cells.iter().map(cell).collect::<Vec<_>>(),
+            Value::Object(map) => columns.iter().map(|c| map.get(c).map(cell).unwrap_or_default()).collect(),
+            _ => return Err("each row must be an array or an object".to_string()),
+        });
+    }
+    let count = body.iter().map(Vec::len).chain(std::iter::once(columns.len())).max().unwrap_or(0);
+    let mut widths = vec![0; count];
+    for row in std::iter::once(&columns).chain(body.iter()) {
+        for (i, text) in row.iter().enumerate() {
+            widths[i] = widths[i].max(text.width());
+        }
+    }
+    let render = |row: &[String]| {
+        let cells: Vec<String> = (0..count)
+            .map(|i| {
+                let text = row.get(i).map(String::as_str).unwrap_or("");
+                format!("{}{}", text, " ".repeat(widths[i] - text.width()))
+            })
+            .collect();
+        cells.join(" | ").trim_end().to_string()
+    };
+    let mut lines = Vec::with_capacity(body.len() + 2);
+    if !columns.is_empty() {
+        lines.push(render(&columns));
+        lines.push(widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("-+-"));
+    }
+    lines.extend(body.iter().map(|row| render(row)));
+    Ok(lines.join("\n"))
+}
+
+fn format_escape(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    if let Some(text) = host::string_arg(scope, &args, 0, "text") {
+        host::return_string(scope, &mut rv, &escape_markdown(&text));
+    }
+}
+
+fn format_codeblock(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let text = match host::string_arg(scope, &args, 0, "text") {
+        Some(text) => text,
+        None => return,
+    };
+    let lang = if args.get(1).is_undefined() {
+        String::new()
+    } else {
+        match host::string_arg(scope, &args, 1, "lang") {
+            Some(lang) => lang,
+            None => return,
+        }
+    };
+    host::return_string(scope, &mut rv, &codeblock(&text, &lang));
+}
+
+fn format_table(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let rows = match host::to_json(scope, args.get(0)) {
+        Some(rows) => rows,
+        None => return host::throw_type_error(scope, "rows must be JSON-serializable"),
+    };
+    let options = match host::options_arg(scope, &args, 1) {
+        Some(options) => options,
+        None => return,
+    };
+    match table(&rows, options.get("header")) {
+        Ok(text) => host::return_string(scope, &mut rv, &text),
+        Err(e) => host::throw_type_error(scope, &e),
+    }
+}
+
+fn format_truncate(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let text = match host::string_arg(scope, &args, 0, "text") {
+        Some(text) => text,
+        None => return,
+    };
+    let max = match args.get(1).number_value(scope) {
+        Some(max) if args.get(1).is_number() && max >= 0.0 && max.fract() == 0.0 => max as usize,
+        _ => return host::throw_type_error(scope, "max must be a non-negative integer"),
+    };
+    let options = match host::options_arg(scope, &args, 2) {
+        Some(options) => options,
+        None => return,
+    };
+    let unit = match options.get("unit") {
+        None => Unit::Graphemes,
+        Some(unit) => match unit.as_str().and_then(Unit::parse) {
+            Some(unit) => unit,
+            None => return host::throw_type_error(scope, "unit must be \"graphemes\", \"utf16\" or \"bytes\""),
+        },
+    };
+    let ellipsis = match options.get("ellipsis") {
+        None => "\u{2026}",
+        Some(Value::String(s)) => s.as_str(),
+        Some(_) => return host::throw_type_error(scope, "ellipsis must be a string"),
+    };
+    host::return_string(scope, &mut rv, &truncate(&text, max, unit, ellipsis));
+}
+
+fn format_normalize(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let text = match host::string_arg(scope, &args, 0, "text") {
+        Some(text) => text,
+        None => return,
+    };
+    let form = if args.get(1).is_undefined() {
+        "NFC".to_string()
+    } else {
+        match host::string_arg(scope, &args, 1, "form") {
+            Some(form) => form,
+            None => return,
+        }
+    };
+    match normalize(&text, &form) {
+        Some(text) => host::return_string(scope, &mut rv, &text),
+        None => host::throw_type_error(scope, "form must be one of NFC, NFD, NFKC, NFKD"),
+    }
+}
+
+fn format_render_markdown(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let text = match host::string_arg(scope, &args, 0, "text") {
+        Some(text) => text,
+        None => return,
+    };
+    let options = match host::options_arg(scope, &args, 1) {
+        Some(options) => options,
+        None => return,
+    };
+    let platform = match options.get("platform") {
+        None => Platform::Discord,
+        Some(platform) => match platform.as_str().and_then(Platform::parse) {
+            Some(platform) => platform,
+            None => return host::throw_type_error(scope, "platform must be \"discord\", \"slack\" or \"plain\""),
+        },
+    };
+    host::return_string(scope, &mut rv, &markdown::render(&text, platform));
+}
+
+pub fn install(scope: &mut rusty_v8::HandleScope) {
+    let object = rusty_v8::Object::new(scope);
+    host::set_function(scope, object, "escape", format_escape);
+    host::set_function(scope, object, "codeblock", format_codeblock);
+    host::set_function(scope, object, "table", format_table);
+    host::set_function(scope, object, "truncate", format_truncate);
+    host::set_function(scope, object, "normalize", format_normalize);
+    host::set_function(scope, object, "renderMarkdown", format_render_markdown);
+    host::set_global(scope, "format", object.into());
+}
@@ -0,0 +1,113 @@
+use std::panic::{self, AssertUnwindSafe};
+
+/// Left on the isolate when a host callback panicked. The panic is caught at
+/// the callback boundary, because unwinding through V8 would abort the
+/// process, and the run is reported as an internal error instead.
+pub struct HostPanic;
+
+fn contain(scope: &mut rusty_v8::HandleScope, res: std::thread::Result<()>) {
+    if res.is_err() {
+        scope.set_slot(HostPanic);
+        scope.terminate_execution();
+    }
+}
+
+pub fn guard<F>(callback: F) -> impl Fn(&mut rusty_v8::HandleScope, rusty_v8::FunctionCallbackArguments, rusty_v8::ReturnValue) + Copy
+where
+    F: Fn(&mut rusty_v8::HandleScope, rusty_v8::FunctionCallbackArguments, rusty_v8::ReturnValue) + Copy,
+{
+    move |scope, args, rv| {
+        let res = panic::catch_unwind(AssertUnwindSafe(|| callback(scope, args, rv)));
+        contain(scope, res);
+    }
+}
+
+pub fn guard_getter<F>(
+    getter: F,
+) -> impl Fn(&mut rusty_v8::HandleScope, rusty_v8::Local<rusty_v8::Name>, rusty_v8::PropertyCallbackArguments, rusty_v8::ReturnValue) + Copy
+where
+    F: Fn(&mut rusty_v8::HandleScope, rusty_v8::Local<rusty_v8::Name>, rusty_v8::PropertyCallbackArguments, rusty_v8::ReturnValue) + Copy,
+{
+    move |scope, key, args, rv| {
+        let res = panic::catch_unwind(AssertUnwindSafe(|| getter(scope, key, args, rv)));
+        contain(scope, res);
+    }
+}
+
+pub fn panicked(isolate: &mut rusty_v8::Isolate) -> bool {
+    isolate.remove_slot::<HostPanic>().is_some()
+}
+
+pub fn throw_error(scope: &mut rusty_v8::HandleScope, message: &str) {
+    let message = rusty_v8::String::new(scope, message).unwrap_or_else(|| rusty_v8::String::empty(scope));
+    let exception = rusty_v8::Exception::error(scope, message);
+    scope.throw_exception(exception);
+}
+
+pub fn throw_type_error(scope: &mut rusty_v8::HandleScope, message: &str) {
+    let message = rusty_v8::String::new(scope, message).unwrap_or_else(|| rusty_v8::String::empty(scope));
+    let exception = rusty_v8::Exception::type_error(scope, message);
+    scope.throw_exception(exception);
+}
+
+pub fn throw_range_error(scope: &mut rusty_v8::HandleScope, message: &str) {
+    let message = rusty_v8::String::new(scope, message).unwrap_or_else(|| rusty_v8::String::empty(scope));
+    let exception = rusty_v8::Exception::range_error(scope, message);
+    scope.throw_exception(exception);
+}
+
+pub fn set_global(scope: &mut rusty_v8::HandleScope, name: &str, value: rusty_v8::Local<rusty_v8::Value>) {
+    let global = scope.get_current_context().global(scope);
+    let key = rusty_v8::String::new(scope, name).unwrap();
+    global.set(scope, key.into(), value);
+}
+
+pub fn set_function(
+    scope: &mut rusty_v8::HandleScope,
+    object: rusty_v8::Local<rusty_v8::Object>,
+    name: &str,
+    callback: impl Fn(&mut rusty_v8::HandleScope, rusty_v8::FunctionCallbackArguments, rusty_v8::ReturnValue) + Copy,
+) {
+    let key = rusty_v8::String::new(scope, name).unwrap();
+    let function = rusty_v8::Function::new(scope, guard(callback)).unwrap();
+    object.set(scope, key.into(), function.into());
+}
+
+pub fn string_arg(scope: &mut rusty_v8::HandleScope, args: &rusty_v8::FunctionCallbackArguments, i: i32, what: &str) -> Option<String> {
+    let value = args.get(i);
+    if value.is_string() {
+        Some(value.to_rust_string_lossy(scope))
+    } else {
+        throw_type_error(scope, &format!("{} must be a string", what));
+        None
+    }
+}
+
+pub fn to_json(scope: &mut rusty_v8::HandleScope, value: rusty_v8::Local<rusty_v8::Value>) -> Option<serde_json::Value> {
+    let json = rusty_v8::json::stringify(scope, value)?;
+    serde_json::from_str(&json.to_rust_string_lossy(scope)).ok()
+}
+
+pub fn from_json<'s>(scope: &mut rusty_v8::HandleScope<'s>, value: &serde_json::Value) -> Option<rusty_v8::Local<'s, rusty_v8::Value>> {
+    let text = rusty_v8::String::new(scope, &value.to_string())?;
+    rusty_v8::json::parse(scope, text)
+}
+
+pub fn options_arg(scope: &mut rusty_v8::HandleScope, args: &rusty_v8::FunctionCallbackArguments, i: i32) -> Option<serde_json::Value> {
+    let value = args.get(i);
+    if value.is_undefined() {
+        return Some(serde_json::Value::Null);
+    }
+    let options = to_json(scope, value).filter(serde_json::Value::is_object);
+    if options.is_none() {
+        throw_type_error(scope, "options must be a plain object");
+    }
+    options
+}
+
+pub fn return_string(scope: &mut rusty_v8::HandleScope, rv: &mut rusty_v8::ReturnValue, s: &str) {
+    match rusty_v8::String::new(scope, s) {
+        Some(s) => rv.set(s.into()),
+        None => throw_error(scope, "String too long"),
+    }
+}
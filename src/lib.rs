@@ -0,0 +1,54 @@
+#[macro_use]
+pub mod log;
+#[cfg(feature = "v8")]
+pub mod bench;
+#[cfg(feature = "boa")]
+mod boa;
+#[cfg(feature = "v8")]
+mod calc;
+#[cfg(feature = "v8")]
+mod capability;
+#[cfg(feature = "chart")]
+mod chart;
+mod compression;
+pub mod config;
+#[cfg(feature = "v8")]
+mod console;
+#[cfg(feature = "v8")]
+mod ctx;
+#[cfg(feature = "v8")]
+mod data;
+#[cfg(feature = "v8")]
+mod datasets;
+pub mod engine;
+#[cfg(feature = "async")]
+pub mod executor;
+pub mod filter;
+#[cfg(feature = "v8")]
+mod format;
+#[cfg(feature = "v8")]
+#[doc(hidden)]
+pub mod host;
+#[cfg(feature = "v8")]
+mod markdown;
+pub mod messages;
+pub mod moderation;
+#[cfg(feature = "v8")]
+mod profiler;
+pub mod protocol;
+#[cfg(feature = "v8")]
+mod random;
+#[cfg(feature = "v8")]
+mod search;
+#[cfg(feature = "v8")]
+mod serialize;
+#[cfg(feature = "v8")]
+mod trace;
+#[cfg(feature = "v8")]
+mod v8;
+#[cfg(feature = "v8")]
+mod vfs;
+
+pub use engine::{Cancel, Engine};
+#[cfg(feature = "v8")]
+pub use v8::{exec_v8, exec_v8_with, fuzz_exec, initialize, V8Engine};
@@ -0,0 +1,77 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Clone, Copy)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+}
+
+impl Level {
+    pub fn parse(s: &str) -> Option<Level> {
+        match s {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Warn as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+thread_local! {
+    static REQUEST_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub fn with_request_id<T>(id: Option<&str>, f: impl FnOnce() -> T) -> T {
+    let outer = REQUEST_ID.with(|r| r.replace(id.map(str::to_string)));
+    let res = f();
+    REQUEST_ID.with(|r| *r.borrow_mut() = outer);
+    res
+}
+
+pub fn write(level: Level, args: std::fmt::Arguments) {
+    if level as u8 <= LEVEL.load(Ordering::Relaxed) {
+        REQUEST_ID.with(|r| match &*r.borrow() {
+            Some(id) => eprintln!("[{}] [{}] {}", level.name(), id, args),
+            None => eprintln!("[{}] {}", level.name(), args),
+        });
+    }
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Error, format_args!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Warn, format_args!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Info, format_args!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Debug, format_args!($($arg)*)) };
+}
@@ -1,71 +1,56 @@
-use serde::{Serialize, Deserialize};
+use bot_script_runner::config::Config;
+use bot_script_runner::protocol::{self, emit, ErrorCode, ErrorKind, Input, ScriptResult};
+use std::time::Instant;
+use bot_script_runner::{bench, engine, error, info, log};
 
-fn get_error(scope: &mut rusty_v8::TryCatch<rusty_v8::HandleScope>) -> String {
-    if let Some(exp) = scope.exception() {
-        rusty_v8::Exception::create_message(scope, exp).get(scope).to_rust_string_lossy(scope)
-    } else {
-        "".to_string()
-    }
-}
-
-fn exec_v8(input: &str) -> Result<String, String> {
-    let mut isolate = rusty_v8::Isolate::new(Default::default());
-    let base_scope = &mut rusty_v8::HandleScope::new(&mut isolate);
-    let context = rusty_v8::Context::new(base_scope);
-    let context_scope = &mut rusty_v8::ContextScope::new(base_scope, context);
-    let scope = &mut rusty_v8::TryCatch::new(context_scope);
-    let code = rusty_v8::String::new(scope, &input).unwrap();
-    code.to_rust_string_lossy(scope);
-    if let Some(script) = rusty_v8::Script::compile(scope, code, None) {
-        if let Some(val) = script.run(scope) {
-            if let Some(result) = val.to_string(scope) {
-                Ok(result.to_rust_string_lossy(scope))
-            } else {
-                Err(get_error(scope))
-            }            
-        } else {
-            Err(get_error(scope))
+fn main() {
+    let started = Instant::now();
+    protocol::install_panic_hook();
+    let (bench, config) = match bench::split_args(std::env::args().skip(1).collect())
+        .and_then(|(bench, args)| Config::from_args(args.into_iter()).map(|c| (bench, c)))
+    {
+        Ok(c) => c,
+        Err(e) => {
+            error!("{}", e);
+            emit(&ScriptResult::err(ErrorCode::BadArgs, "Error"));
+            std::process::exit(ErrorKind::Protocol.exit_code());
         }
-    } else {
-        Err(get_error(scope))
+    };
+    if let Some(level) = config.log_level {
+        log::set_level(level);
+    }
+    if let Err(e) = bot_script_runner::initialize(&config.v8_flags) {
+        error!("{}", e);
+        emit(&ScriptResult::err(ErrorCode::BadArgs, "Error"));
+        std::process::exit(ErrorKind::Protocol.exit_code());
     }
-}
-
-#[derive(Serialize)]
-struct ScriptResult {
-    result: String,
-    error: String
-}
-#[derive(Deserialize)]
-struct Input {
-    script: String
-}
 
-fn main() {
-    let platform = rusty_v8::new_default_platform(0, false).make_shared();
-    rusty_v8::V8::initialize_platform(platform);
-    rusty_v8::V8::initialize();
-    
-    let mut input_str = String::new();
-    if let Err(_) = std::io::stdin().read_line(&mut input_str) {
-        let result = serde_json::to_string(&ScriptResult {
-            result: "".to_string(),
-            error: "Error".to_string()
-        }).unwrap();
-        print!("{}", result)
+    if let Some(options) = bench {
+        if let Err(e) = bench::run(&options, &config) {
+            error!("{}", e);
+            std::process::exit(ErrorKind::Protocol.exit_code());
+        }
+        return;
     }
-    let input: Input = serde_json::from_str(&input_str).unwrap();
-    let script = input.script;
-    let res = match exec_v8(&script) {
-        Ok(s) => ScriptResult {
-            result: s,
-            error: "".to_string()
-        },
-        Err(s) => ScriptResult {
-            result: "".to_string(),
-            error: s
+    let input = match protocol::read_input(std::io::stdin().lock(), config.max_script_bytes) {
+        Ok(input) => Input { queued_at: Some(started), ..input },
+        Err(e) => {
+            emit(&ScriptResult::from(e));
+            std::process::exit(ErrorKind::Protocol.exit_code());
         }
     };
-    let result = serde_json::to_string(&res).unwrap();
-    print!("{}", result);
-}
\ No newline at end of file
+    let engine = match engine::select(config.engine) {
+        Some(engine) => engine,
+        None => {
+            error!("engine not available: {:?}", config.engine);
+            emit(&ScriptResult::err(ErrorCode::BadArgs, "Error"));
+            std::process::exit(ErrorKind::Protocol.exit_code());
+        }
+    };
+    let res = protocol::contain(|| engine::run(&*engine, &input, &config, None)).unwrap_or_else(ScriptResult::from);
+    if let Some(kind) = res.error_kind {
+        info!("{:?} error: {}", kind, res.error);
+    }
+    emit(&res);
+    std::process::exit(res.error_kind.map_or(0, ErrorKind::exit_code));
+}
@@ -0,0 +1,222 @@
+use crate::format::{codeblock, escape_markdown};
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Platform {
+    Discord,
+    Slack,
+    Plain,
+}
+
+impl Platform {
+    pub fn parse(s: &str) -> Option<Platform> {
+        match s {
+            "discord" => Some(Platform::Discord),
+            "slack" => Some(Platform::Slack),
+            "plain" => Some(Platform::Plain),
+            _ => None,
+        }
+    }
+
+    fn text(self, text: &str) -> String {
+        match self {
+            Platform::Discord => escape_markdown(text).replace('@', "@\u{200b}"),
+            Platform::Slack => text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;"),
+            Platform::Plain => text.replace('@', "@\u{200b}"),
+        }
+    }
+
+    fn marker(self, tag: &TagEnd) -> &'static str {
+        match (self, tag) {
+            (Platform::Plain, _) => "",
+            (Platform::Discord, TagEnd::Emphasis) | (Platform::Slack, TagEnd::Emphasis) => "_",
+            (Platform::Discord, TagEnd::Strong) => "**",
+            (Platform::Slack, TagEnd::Strong) => "*",
+            (Platform::Discord, TagEnd::Strikethrough) => "~~",
+            (Platform::Slack, TagEnd::Strikethrough) => "~",
+            _ => "",
+        }
+    }
+}
+
+fn safe_url(url: &str) -> bool {
+    (url.starts_with("https://") || url.starts_with("http://")) && !url.contains(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '|'))
+}
+
+struct Renderer {
+    platform: Platform,
+    blocks: Vec<String>,
+    lists: Vec<Option<u64>>,
+    links: Vec<String>,
+    code: Option<String>,
+    item_started: bool,
+}
+
+impl Renderer {
+    fn out(&mut self) -> &mut String {
+        self.blocks.last_mut().unwrap()
+    }
+
+    fn block_break(&mut self) {
+        let out = self.out();
+        if !out.is_empty() && !out.ends_with("\n\n") {
+            out.push_str(if out.ends_with('\n') { "\n" } else { "\n\n" });
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        let platform = self.platform;
+        let item_started = std::mem::take(&mut self.item_started);
+        match tag {
+            // A loose list wraps each item in a paragraph; keep its text on the bullet's line.
+            Tag::Paragraph if item_started => {}
+            Tag::Paragraph => self.block_break(),
+            Tag::Heading { level, .. } => {
+                self.block_break();
+                let prefix = match (platform, level as usize) {
+                    (Platform::Discord, n) => format!("{} ", "#".repeat(n.min(3))),
+                    (Platform::Slack, _) => "*".to_string(),
+                    (Platform::Plain, _) => String::new(),
+                };
+                self.out().push_str(&prefix);
+            }
+            Tag::BlockQuote(_) => {
+                self.block_break();
+                self.blocks.push(String::new());
+            }
+            Tag::CodeBlock(kind) => {
+                self.block_break();
+                let lang = match kind {
+                    CodeBlockKind::Fenced(lang) => lang.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some(lang);
+                self.blocks.push(String::new());
+            }
+            Tag::List(start) => {
+                if self.lists.is_empty() {
+                    self.block_break();
+                }
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                let depth = self.lists.len().saturating_sub(1);
+                let bullet = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                let out = self.out();
+                if !out.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push_str(&"  ".repeat(depth));
+                out.push_str(&bullet);
+                self.item_started = true;
+            }
+            Tag::Emphasis => self.out().push_str(platform.marker(&TagEnd::Emphasis)),
+            Tag::Strong => self.out().push_str(platform.marker(&TagEnd::Strong)),
+            Tag::Strikethrough => self.out().push_str(platform.marker(&TagEnd::Strikethrough)),
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                self.links.push(dest_url.to_string());
+                self.blocks.push(String::new());
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        let platform = self.platform;
+        match tag {
+            TagEnd::Heading(_) => {
+                if platform == Platform::Slack {
+                    self.out().push('*');
+                }
+                self.out().push('\n');
+            }
+            TagEnd::Paragraph => self.out().push('\n'),
+            TagEnd::BlockQuote(_) => {
+                let quoted = self.blocks.pop().unwrap();
+                let prefix = if platform == Platform::Plain { "" } else { "> " };
+                let quoted: Vec<_> = quoted.trim_end().lines().map(|l| format!("{}{}", prefix, l)).collect();
+                let out = self.out();
+                out.push_str(&quoted.join("\n"));
+                out.push('\n');
+            }
+            TagEnd::CodeBlock => {
+                let body = self.blocks.pop().unwrap();
+                let lang = self.code.take().unwrap_or_default();
+                let rendered = match platform {
+                    Platform::Discord => codeblock(&body, &lang),
+                    Platform::Slack => codeblock(&platform.text(&body), ""),
+                    Platform::Plain => body.trim_end().to_string(),
+                };
+                let out = self.out();
+                out.push_str(&rendered);
+                out.push('\n');
+            }
+            TagEnd::List(_) => {
+                self.lists.pop();
+                if self.lists.is_empty() && !self.out().ends_with('\n') {
+                    self.out().push('\n');
+                }
+            }
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
+                let marker = platform.marker(&tag);
+                self.out().push_str(marker);
+            }
+            TagEnd::Link | TagEnd::Image => {
+                let text = self.blocks.pop().unwrap();
+                let url = self.links.pop().unwrap();
+                let rendered = match platform {
+                    _ if !safe_url(&url) => text,
+                    Platform::Discord => format!("[{}](<{}>)", text, url),
+                    Platform::Slack => format!("<{}|{}>", url, text),
+                    Platform::Plain => format!("{} ({})", text, url),
+                };
+                self.out().push_str(&rendered);
+            }
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.code.is_some() {
+            self.out().push_str(text);
+        } else {
+            let text = self.platform.text(text);
+            self.out().push_str(&text);
+        }
+    }
+
+    fn inline_code(&mut self, code: &str) {
+        let platform = self.platform;
+        let rendered = match platform {
+            Platform::Plain => code.to_string(),
+            Platform::Discord => format!("`{}`", code.replace('`', "\u{2018}")),
+            Platform::Slack => format!("`{}`", platform.text(&code.replace('`', "\u{2018}"))),
+        };
+        self.out().push_str(&rendered);
+    }
+}
+
+pub fn render(text: &str, platform: Platform) -> String {
+    let mut renderer = Renderer { platform, blocks: vec![String::new()], lists: Vec::new(), links: Vec::new(), code: None, item_started: false };
+    for event in Parser::new_ext(text, Options::ENABLE_STRIKETHROUGH) {
+        match event {
+            Event::Start(tag) => renderer.start(tag),
+            Event::End(tag) => renderer.end(tag),
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => renderer.text(&text),
+            Event::Code(code) => renderer.inline_code(&code),
+            Event::SoftBreak | Event::HardBreak => renderer.out().push('\n'),
+            Event::Rule => {
+                renderer.block_break();
+                renderer.out().push_str("\u{2014}\u{2014}\u{2014}\n");
+            }
+            _ => {}
+        }
+    }
+    renderer.blocks.swap_remove(0).trim_end().to_string()
+}
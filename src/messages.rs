@@ -0,0 +1,47 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Lang {
+    #[default]
+    En,
+    Ja,
+}
+
+const JA: &[(&str, &str)] = &[
+    ("Blocked by moderation", "モデレーションによりブロックされました"),
+    ("Cancelled", "キャンセルされました"),
+    ("Deadline exceeded", "期限を過ぎました"),
+    ("Error", "エラー"),
+    ("Internal error", "内部エラー"),
+    ("Invalid input", "入力が不正です"),
+    ("Memory limit", "メモリ制限を超えました"),
+    ("Permission denied", "権限がありません"),
+    ("Result too large", "結果が大きすぎます"),
+    ("Script too large", "スクリプトが大きすぎます"),
+    ("Timeout", "タイムアウトしました"),
+    ("Unknown dataset", "不明なデータセット"),
+    ("Unknown entry", "不明なエントリ関数"),
+    ("Unsupported", "未対応の機能です"),
+];
+
+fn catalog(lang: Lang) -> &'static [(&'static str, &'static str)] {
+    match lang {
+        Lang::En => &[],
+        Lang::Ja => JA,
+    }
+}
+
+pub fn localize(lang: Lang, message: &str) -> String {
+    let (key, arg) = match message.split_once(": ") {
+        Some((key, arg)) => (key, Some(arg)),
+        None => (message, None),
+    };
+    match catalog(lang).iter().find(|(en, _)| *en == key) {
+        Some((_, text)) => match arg {
+            Some(arg) => format!("{}: {}", text, arg),
+            None => text.to_string(),
+        },
+        None => message.to_string(),
+    }
+}
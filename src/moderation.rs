@@ -0,0 +1,96 @@
+use crate::protocol::{ErrorCode, ScriptResult};
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Verdict {
+    Allow,
+    Annotate { labels: Value },
+    Veto { reason: String },
+}
+
+pub trait OutputModerator: Send + Sync {
+    fn name(&self) -> &str;
+    fn moderate(&self, res: &ScriptResult) -> Result<Verdict, String>;
+}
+
+pub struct CommandModerator {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl CommandModerator {
+    pub fn parse(command: &str, timeout: Duration) -> Result<CommandModerator, String> {
+        let mut words = command.split_whitespace().map(str::to_string);
+        let program = words.next().ok_or("--moderator requires a command")?;
+        Ok(CommandModerator { program, args: words.collect(), timeout })
+    }
+}
+
+impl OutputModerator for CommandModerator {
+    fn name(&self) -> &str {
+        &self.program
+    }
+
+    fn moderate(&self, res: &ScriptResult) -> Result<Verdict, String> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| e.to_string())?;
+        let doc = serde_json::to_vec(res).map_err(|e| e.to_string())?;
+        let mut stdin = child.stdin.take().unwrap();
+        std::thread::spawn(move || stdin.write_all(&doc));
+        let mut stdout = child.stdout.take().unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut out = Vec::new();
+            let _ = tx.send(stdout.read_to_end(&mut out).map(|_| out));
+        });
+        let out = match rx.recv_timeout(self.timeout) {
+            Ok(out) => out.map_err(|e| e.to_string()),
+            Err(_) => {
+                let _ = child.kill();
+                Err(format!("timed out after {} ms", self.timeout.as_millis()))
+            }
+        };
+        let status = child.wait().map_err(|e| e.to_string())?;
+        let out = out?;
+        if !status.success() {
+            return Err(format!("exited with {}", status));
+        }
+        serde_json::from_slice(&out).map_err(|e| e.to_string())
+    }
+}
+
+fn veto(res: &mut ScriptResult, reason: Option<String>) {
+    let request_id = res.request_id.take();
+    let stats = std::mem::take(&mut res.stats);
+    *res = ScriptResult::err(ErrorCode::Moderated, "Blocked by moderation");
+    res.error_detail = reason.map(|reason| serde_json::json!({ "reason": reason }));
+    res.request_id = request_id;
+    res.stats = stats;
+}
+
+pub fn apply(moderator: &dyn OutputModerator, fail_open: bool, res: &mut ScriptResult) {
+    match moderator.moderate(res) {
+        Ok(Verdict::Allow) => {}
+        Ok(Verdict::Annotate { labels }) => res.moderation = Some(labels),
+        Ok(Verdict::Veto { reason }) => {
+            info!("output vetoed by {}: {}", moderator.name(), reason);
+            veto(res, Some(reason));
+        }
+        Err(e) if fail_open => warn!("moderator {} failed, allowing output: {}", moderator.name(), e),
+        Err(e) => {
+            error!("moderator {} failed, blocking output: {}", moderator.name(), e);
+            veto(res, None);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use rusty_v8::inspector::{
+    ChannelBase, ChannelImpl, StringBuffer, StringView, V8Inspector, V8InspectorClientBase, V8InspectorClientImpl,
+    V8InspectorSession,
+};
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+const CONTEXT_GROUP_ID: i32 = 1;
+const SAMPLING_INTERVAL_BYTES: u64 = 1024;
+const TOP_SITES: usize = 10;
+// Each tree level nests an object and a children array, so this keeps the
+// walk well inside serde_json's recursion limit of 128.
+const MAX_DEPTH: usize = 48;
+
+struct Client {
+    base: V8InspectorClientBase,
+}
+
+impl V8InspectorClientImpl for Client {
+    fn base(&self) -> &V8InspectorClientBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut V8InspectorClientBase {
+        &mut self.base
+    }
+}
+
+struct Channel {
+    base: ChannelBase,
+    responses: HashMap<i32, String>,
+}
+
+impl ChannelImpl for Channel {
+    fn base(&self) -> &ChannelBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ChannelBase {
+        &mut self.base
+    }
+
+    fn send_response(&mut self, call_id: i32, message: rusty_v8::UniquePtr<StringBuffer>) {
+        self.responses.insert(call_id, message.unwrap().string().to_string());
+    }
+
+    fn send_notification(&mut self, _message: rusty_v8::UniquePtr<StringBuffer>) {}
+
+    fn flush_protocol_notifications(&mut self) {}
+}
+
+pub struct AllocProfiler {
+    session: rusty_v8::UniqueRef<V8InspectorSession>,
+    _inspector: rusty_v8::UniqueRef<V8Inspector>,
+    channel: Box<Channel>,
+    _client: Box<Client>,
+    next_id: i32,
+}
+
+struct Profile {
+    sites: Value,
+    truncated: bool,
+}
+
+impl AllocProfiler {
+    pub fn start(scope: &mut rusty_v8::HandleScope, context: rusty_v8::Local<rusty_v8::Context>) -> AllocProfiler {
+        let mut client = Box::new(Client {
+            base: V8InspectorClientBase::new::<Client>(),
+        });
+        let mut inspector = V8Inspector::create(scope, &mut *client);
+        inspector.context_created(context, CONTEXT_GROUP_ID, StringView::from(&b"script"[..]));
+        let mut channel = Box::new(Channel {
+            base: ChannelBase::new::<Channel>(),
+            responses: HashMap::new(),
+        });
+        let session = inspector.connect(CONTEXT_GROUP_ID, &mut *channel, StringView::from(&b"{}"[..]));
+        let mut profiler = AllocProfiler {
+            session,
+            _inspector: inspector,
+            channel,
+            _client: client,
+            next_id: 0,
+        };
+        profiler.call("HeapProfiler.enable", json!({}));
+        profiler.call("HeapProfiler.startSampling", json!({ "samplingInterval": SAMPLING_INTERVAL_BYTES }));
+        profiler
+    }
+
+    fn dispatch(&mut self, method: &str, params: Value) -> Option<String> {
+        self.next_id += 1;
+        let message = json!({ "id": self.next_id, "method": method, "params": params }).to_string();
+        self.session.dispatch_protocol_message(StringView::from(message.as_bytes()));
+        self.channel.responses.remove(&self.next_id)
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Option<Value> {
+        let response = self.dispatch(method, params)?;
+        let mut response: Value = serde_json::from_str(&response).ok()?;
+        match response.get_mut("result") {
+            Some(result) => Some(result.take()),
+            None => {
+                warn!("{} failed: {}", method, response);
+                None
+            }
+        }
+    }
+
+    pub fn finish(mut self, isolate: &mut rusty_v8::Isolate) {
+        let response = match self.dispatch("HeapProfiler.stopSampling", json!({})) {
+            Some(response) => response,
+            None => return,
+        };
+        let mut sites = Sites::default();
+        let head = Field("head", Node { sites: &mut sites, depth: 0 });
+        let walked = Field("result", Field("profile", head)).deserialize(&mut serde_json::Deserializer::from_str(&response));
+        match walked {
+            Ok(()) if sites.found => {
+                let truncated = sites.truncated;
+                isolate.set_slot(Profile { sites: sites.top(), truncated });
+            }
+            Ok(()) => warn!("HeapProfiler.stopSampling failed: {}", response),
+            Err(e) => warn!("HeapProfiler.stopSampling returned an unreadable profile: {}", e),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Sites {
+    bytes: HashMap<(String, String, i64, i64), u64>,
+    found: bool,
+    truncated: bool,
+}
+
+impl Sites {
+    fn top(self) -> Value {
+        let mut sites: Vec<_> = self.bytes.into_iter().collect();
+        sites.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        sites
+            .into_iter()
+            .take(TOP_SITES)
+            .map(|((function, url, line, column), bytes)| {
+                json!({ "function": function, "url": url, "line": line, "column": column, "bytes": bytes })
+            })
+            .collect()
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct CallFrame {
+    function_name: String,
+    url: String,
+    line_number: i64,
+    column_number: i64,
+}
+
+/// Descends into one field of an object and skips the rest.
+struct Field<S>(&'static str, S);
+
+impl<'de, S: DeserializeSeed<'de, Value = ()>> DeserializeSeed<'de> for Field<S> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de, Value = ()>> Visitor<'de> for Field<S> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an object with {}", self.0)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let Field(name, seed) = self;
+        let mut seed = Some(seed);
+        while let Some(key) = map.next_key::<String>()? {
+            match seed.take() {
+                Some(s) if key == name => map.next_value_seed(s)?,
+                other => {
+                    seed = other;
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sums self sizes per call site while reading the sampling tree, without
+/// building it. Subtrees below MAX_DEPTH are skipped and flagged.
+struct Node<'a> {
+    sites: &'a mut Sites,
+    depth: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for Node<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Node<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sampling heap profile node")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let Node { sites, depth } = self;
+        sites.found = true;
+        let mut frame = CallFrame::default();
+        let mut size = 0;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "callFrame" => frame = map.next_value()?,
+                "selfSize" => size = map.next_value()?,
+                "children" if depth < MAX_DEPTH => map.next_value_seed(Children { sites: &mut *sites, depth: depth + 1 })?,
+                "children" => {
+                    let children: Vec<IgnoredAny> = map.next_value()?;
+                    sites.truncated |= !children.is_empty();
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        if size > 0 {
+            let name = if frame.function_name.is_empty() { "(anonymous)".to_string() } else { frame.function_name };
+            let key = (name, frame.url, frame.line_number + 1, frame.column_number + 1);
+            *sites.bytes.entry(key).or_insert(0) += size;
+        }
+        Ok(())
+    }
+}
+
+struct Children<'a> {
+    sites: &'a mut Sites,
+    depth: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for Children<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Children<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of profile nodes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while seq.next_element_seed(Node { sites: &mut *self.sites, depth: self.depth })?.is_some() {}
+        Ok(())
+    }
+}
+
+pub fn take(isolate: &mut rusty_v8::Isolate) -> Option<(Value, bool)> {
+    isolate.remove_slot::<Profile>().map(|p| (p.sites, p.truncated))
+}
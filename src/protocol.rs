@@ -0,0 +1,371 @@
+use crate::compression::{DecompressError, Envelope};
+use crate::messages::Lang;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Script,
+    Timeout,
+    Oom,
+    Protocol,
+    Internal,
+    Cancelled,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Script => 1,
+            ErrorKind::Protocol => 2,
+            ErrorKind::Timeout => 3,
+            ErrorKind::Oom => 4,
+            ErrorKind::Internal => 5,
+            ErrorKind::Cancelled => 6,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum ErrorCode {
+    #[serde(rename = "E_SYNTAX")]
+    Syntax,
+    #[serde(rename = "E_EXCEPTION")]
+    Exception,
+    #[serde(rename = "E_RESULT_TOO_LARGE")]
+    ResultTooLarge,
+    #[serde(rename = "E_TIMEOUT")]
+    Timeout,
+    #[serde(rename = "E_DEADLINE")]
+    Deadline,
+    #[serde(rename = "E_OOM")]
+    Oom,
+    #[serde(rename = "E_BAD_ARGS")]
+    BadArgs,
+    #[serde(rename = "E_IO")]
+    Io,
+    #[serde(rename = "E_INVALID_INPUT")]
+    InvalidInput,
+    #[serde(rename = "E_SCRIPT_TOO_LARGE")]
+    ScriptTooLarge,
+    #[serde(rename = "E_UNKNOWN_DATASET")]
+    UnknownDataset,
+    #[serde(rename = "E_UNKNOWN_ENTRY")]
+    UnknownEntry,
+    #[serde(rename = "E_INTERNAL")]
+    Internal,
+    #[serde(rename = "E_CANCELLED")]
+    Cancelled,
+    #[serde(rename = "E_MODERATED")]
+    Moderated,
+    #[serde(rename = "E_PERMISSION_DENIED")]
+    PermissionDenied,
+    #[serde(rename = "E_UNSUPPORTED")]
+    Unsupported,
+}
+
+impl ErrorCode {
+    pub fn kind(self) -> ErrorKind {
+        match self {
+            ErrorCode::Syntax
+            | ErrorCode::Exception
+            | ErrorCode::ResultTooLarge
+            | ErrorCode::Moderated
+            | ErrorCode::PermissionDenied => ErrorKind::Script,
+            ErrorCode::Timeout | ErrorCode::Deadline => ErrorKind::Timeout,
+            ErrorCode::Oom => ErrorKind::Oom,
+            ErrorCode::BadArgs
+            | ErrorCode::Io
+            | ErrorCode::InvalidInput
+            | ErrorCode::ScriptTooLarge
+            | ErrorCode::UnknownDataset
+            | ErrorCode::UnknownEntry
+            | ErrorCode::Unsupported => ErrorKind::Protocol,
+            ErrorCode::Internal => ErrorKind::Internal,
+            ErrorCode::Cancelled => ErrorKind::Cancelled,
+        }
+    }
+}
+
+pub struct ExecError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub detail: Option<Value>,
+}
+
+impl ExecError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> ExecError {
+        ExecError {
+            code,
+            message: message.into(),
+            detail: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct LogEntry {
+    pub level: &'static str,
+    pub text: String,
+    pub t_ms: u64,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultFormat {
+    #[default]
+    String,
+    Extended,
+}
+
+#[derive(Serialize, Default)]
+pub struct Stats {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub datasets_accessed: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dataset_paths: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dataset_paths_truncated: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub soft_heap_limit_hit: bool,
+    pub heap_bytes: usize,
+    pub queued_ms: f64,
+    pub compile_ms: f64,
+    pub exec_ms: f64,
+    pub serialize_ms: f64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub apis: Vec<&'static str>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub filter_hits: BTreeMap<&'static str, usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub random: Option<Value>,
+}
+
+#[derive(Serialize)]
+pub struct ScriptResult {
+    pub result: Value,
+    pub error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<ErrorKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_detail: Option<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub logs: Vec<LogEntry>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub logs_truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alloc_profile: Option<Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub alloc_profile_truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host_calls: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moderation: Option<Value>,
+    pub stats: Stats
+}
+
+impl ScriptResult {
+    pub fn ok(result: Value) -> ScriptResult {
+        ScriptResult {
+            result,
+            error: "".to_string(),
+            error_kind: None,
+            error_code: None,
+            error_detail: None,
+            logs: Vec::new(),
+            logs_truncated: false,
+            alloc_profile: None,
+            alloc_profile_truncated: false,
+            host_calls: None,
+            files: None,
+            request_id: None,
+            moderation: None,
+            stats: Stats::default()
+        }
+    }
+
+    pub fn err(code: ErrorCode, error: &str) -> ScriptResult {
+        ScriptResult {
+            result: Value::String("".to_string()),
+            error: error.to_string(),
+            error_kind: Some(code.kind()),
+            error_code: Some(code),
+            error_detail: None,
+            logs: Vec::new(),
+            logs_truncated: false,
+            alloc_profile: None,
+            alloc_profile_truncated: false,
+            host_calls: None,
+            files: None,
+            request_id: None,
+            moderation: None,
+            stats: Stats::default()
+        }
+    }
+}
+
+impl From<ExecError> for ScriptResult {
+    fn from(e: ExecError) -> ScriptResult {
+        let mut res = ScriptResult::err(e.code, &e.message);
+        res.error_detail = e.detail;
+        res
+    }
+}
+
+impl From<Result<Value, ExecError>> for ScriptResult {
+    fn from(res: Result<Value, ExecError>) -> ScriptResult {
+        match res {
+            Ok(s) => ScriptResult::ok(s),
+            Err(e) => ScriptResult::from(e),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Input {
+    pub script: String,
+    #[serde(default)]
+    pub datasets: Vec<String>,
+    #[serde(default)]
+    pub result_format: ResultFormat,
+    #[serde(default)]
+    pub lang: Lang,
+    #[serde(default)]
+    pub profile_alloc: bool,
+    #[serde(default)]
+    pub deadline_unix_ms: Option<u64>,
+    #[serde(default)]
+    pub request_id: Option<String>,
+    #[serde(default)]
+    pub args: Vec<Value>,
+    #[serde(default)]
+    pub entry: Option<String>,
+    #[serde(default)]
+    pub calls: Vec<Call>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    #[serde(default)]
+    pub trace_host_calls: bool,
+    #[serde(default)]
+    pub return_files: bool,
+    #[serde(default)]
+    pub random_seed: Option<u64>,
+    #[serde(skip)]
+    pub queued_at: Option<Instant>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Call {
+    pub entry: String,
+    #[serde(default)]
+    pub args: Vec<Value>,
+}
+
+impl Input {
+    pub fn context(&self) -> Value {
+        serde_json::json!({ "requestId": self.request_id, "permissions": self.permissions })
+    }
+}
+
+const ENVELOPE_BYTES: usize = 64 * 1024;
+
+pub const SCHEMA: &str = include_str!("schema.json");
+
+fn invalid(path: Option<&str>, e: &dyn std::fmt::Display) -> ExecError {
+    warn!("invalid input at {}: {}", path.unwrap_or("."), e);
+    let mut err = ExecError::new(ErrorCode::InvalidInput, "Invalid input");
+    err.detail = Some(serde_json::json!({ "path": path.unwrap_or("."), "message": e.to_string() }));
+    err
+}
+
+fn validate<T: serde::de::DeserializeOwned>(value: Value) -> Result<T, ExecError> {
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let path = e.path().to_string();
+        invalid(Some(&path), e.inner())
+    })
+}
+
+pub fn read_input<R: BufRead>(reader: R, max_script_bytes: usize) -> Result<Input, ExecError> {
+    let limit = max_script_bytes.saturating_add(ENVELOPE_BYTES);
+    let mut line = String::new();
+    if let Err(e) = reader.take(limit as u64).read_line(&mut line) {
+        error!("failed to read input: {}", e);
+        return Err(ExecError::new(ErrorCode::Io, "Error"));
+    }
+    if line.len() >= limit && !line.ends_with('\n') {
+        warn!("input exceeds {} bytes", limit);
+        return Err(ExecError::new(ErrorCode::ScriptTooLarge, "Script too large"));
+    }
+    let mut value: Value = serde_json::from_str(&line).map_err(|e| invalid(None, &e))?;
+    if value.get("compression").is_some() {
+        let envelope: Envelope = validate(value)?;
+        let payload = match envelope.decompress(limit) {
+            Ok(payload) => payload,
+            Err(DecompressError::Invalid(e)) => return Err(invalid(Some("payload"), &e)),
+            Err(DecompressError::TooLarge) => {
+                warn!("decompressed input exceeds {} bytes or the ratio limit", limit);
+                return Err(ExecError::new(ErrorCode::ScriptTooLarge, "Script too large"));
+            }
+        };
+        value = serde_json::from_slice(&payload).map_err(|e| invalid(Some("payload"), &e))?;
+    }
+    let input: Input = validate(value)?;
+    if !input.calls.is_empty() && (input.entry.is_some() || !input.args.is_empty()) {
+        return Err(invalid(Some("calls"), &"cannot be combined with entry or args"));
+    }
+    if input.script.len() > max_script_bytes {
+        warn!("script is {} bytes, limit is {}", input.script.len(), max_script_bytes);
+        return Err(ExecError::new(ErrorCode::ScriptTooLarge, "Script too large"));
+    }
+    Ok(input)
+}
+
+static EMITTED: AtomicBool = AtomicBool::new(false);
+
+pub fn emit(res: &ScriptResult) {
+    if EMITTED.swap(true, Ordering::SeqCst) {
+        error!("result already written, dropping: {}", res.error);
+        return;
+    }
+    let doc = serde_json::to_string(res).unwrap_or_else(|_| r#"{"result":"","error":"Error"}"#.to_string());
+    let mut stdout = std::io::stdout();
+    if stdout.write_all(doc.as_bytes()).and_then(|_| stdout.flush()).is_err() {
+        error!("failed to write result to stdout");
+    }
+}
+
+thread_local! {
+    static CONTAINED: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        error!("panic: {}", info);
+        if !CONTAINED.with(|c| c.get()) {
+            emit(&ScriptResult::err(ErrorCode::Internal, "Internal error"));
+            std::process::exit(ErrorKind::Internal.exit_code());
+        }
+    }));
+}
+
+pub fn contain<T, F: FnOnce() -> T>(f: F) -> Result<T, ExecError> {
+    let outer = CONTAINED.with(|c| c.replace(true));
+    let res = std::panic::catch_unwind(AssertUnwindSafe(f));
+    CONTAINED.with(|c| c.set(outer));
+    res.map_err(|_| ExecError::new(ErrorCode::Internal, "Internal error"))
+}
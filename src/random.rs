@@ -0,0 +1,216 @@
+use crate::host;
+use rand_chacha::ChaCha20Rng;
+use rand_core::{OsRng, RngCore, SeedableRng};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+const MAX_DICE: u64 = 1000;
+const MAX_SIDES: u64 = 1_000_000;
+const SEED_MASK: u64 = (1 << 53) - 1;
+const MAX_ITEMS: u32 = 1_000_000;
+const MAX_RECORDED_ORDER: usize = 100;
+
+pub struct Random {
+    seed: u64,
+    rng: ChaCha20Rng,
+    draws: Vec<Value>,
+    dropped: usize,
+    bytes: usize,
+    max_draws: usize,
+    max_bytes: usize,
+}
+
+type State = Rc<RefCell<Random>>;
+
+impl Random {
+    pub fn new(seed: Option<u64>, max_draws: usize, max_bytes: usize) -> Random {
+        let seed = seed.unwrap_or_else(|| OsRng.next_u64() & SEED_MASK);
+        Random {
+            seed,
+            rng: ChaCha20Rng::seed_from_u64(seed),
+            draws: Vec::new(),
+            dropped: 0,
+            bytes: 0,
+            max_draws,
+            max_bytes,
+        }
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.rng.next_u64();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+
+    fn record(&mut self, draw: Value) {
+        let len = draw.to_string().len();
+        if self.draws.len() < self.max_draws && self.bytes + len <= self.max_bytes {
+            self.bytes += len;
+            self.draws.push(draw);
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    pub fn stats(&self) -> Option<Value> {
+        if self.draws.is_empty() && self.dropped == 0 {
+            return None;
+        }
+        Some(json!({ "seed": self.seed, "draws": self.draws, "dropped": self.dropped }))
+    }
+}
+
+enum Term {
+    Dice { count: u64, sides: u64, sign: i64 },
+    Constant(i64),
+}
+
+fn parse_dice(notation: &str) -> Option<Vec<Term>> {
+    let compact: String = notation.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut terms = Vec::new();
+    let mut dice = 0;
+    let mut rest = compact.as_str();
+    let mut sign = 1;
+    if let Some(r) = rest.strip_prefix('-') {
+        sign = -1;
+        rest = r;
+    }
+    loop {
+        let end = rest.find(['+', '-']).unwrap_or(rest.len());
+        let term = &rest[..end];
+        match term.split_once(['d', 'D']) {
+            Some((count, sides)) => {
+                let count = if count.is_empty() { 1 } else { count.parse::<u64>().ok()? };
+                let sides = sides.parse::<u64>().ok()?;
+                dice += count;
+                if count == 0 || sides == 0 || sides > MAX_SIDES || dice > MAX_DICE {
+                    return None;
+                }
+                terms.push(Term::Dice { count, sides, sign });
+            }
+            None => terms.push(Term::Constant(sign * term.parse::<i32>().ok()? as i64)),
+        }
+        if end == rest.len() {
+            return Some(terms);
+        }
+        sign = if rest[end..].starts_with('-') { -1 } else { 1 };
+        rest = &rest[end + 1..];
+    }
+}
+
+fn array_arg<'s>(scope: &mut rusty_v8::HandleScope, value: rusty_v8::Local<'s, rusty_v8::Value>) -> Option<rusty_v8::Local<'s, rusty_v8::Array>> {
+    let array = match rusty_v8::Local::<rusty_v8::Array>::try_from(value) {
+        Ok(array) => array,
+        Err(_) => {
+            host::throw_type_error(scope, "items must be an array");
+            return None;
+        }
+    };
+    if array.length() > MAX_ITEMS {
+        host::throw_range_error(scope, &format!("items must have at most {} elements", MAX_ITEMS));
+        return None;
+    }
+    Some(array)
+}
+
+fn random_pick(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let items = match array_arg(scope, args.get(0)) {
+        Some(items) => items,
+        None => return,
+    };
+    if items.length() == 0 {
+        host::throw_error(scope, "cannot pick from an empty array");
+        return;
+    }
+    let state = scope.get_slot::<State>().unwrap().clone();
+    let index = state.borrow_mut().below(items.length() as u64) as u32;
+    let item = match items.get_index(scope, index) {
+        Some(item) => item,
+        None => return,
+    };
+    state.borrow_mut().record(json!({ "call": "pick", "length": items.length(), "index": index }));
+    rv.set(item);
+}
+
+fn random_shuffle(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let items = match array_arg(scope, args.get(0)) {
+        Some(items) => items,
+        None => return,
+    };
+    let state = scope.get_slot::<State>().unwrap().clone();
+    let mut order: Vec<u32> = (0..items.length()).collect();
+    for i in (1..order.len()).rev() {
+        let j = state.borrow_mut().below(i as u64 + 1) as usize;
+        order.swap(i, j);
+    }
+    let mut shuffled = Vec::with_capacity(order.len());
+    for &i in &order {
+        match items.get_index(scope, i) {
+            Some(item) => shuffled.push(item),
+            None => return,
+        }
+    }
+    let recorded = &order[..order.len().min(MAX_RECORDED_ORDER)];
+    let mut draw = json!({ "call": "shuffle", "length": order.len(), "order": recorded });
+    if recorded.len() < order.len() {
+        draw["order_truncated"] = Value::Bool(true);
+    }
+    state.borrow_mut().record(draw);
+    rv.set(rusty_v8::Array::new_with_elements(scope, &shuffled).into());
+}
+
+fn random_dice(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let notation = match host::string_arg(scope, &args, 0, "notation") {
+        Some(notation) => notation,
+        None => return,
+    };
+    let terms = match parse_dice(&notation) {
+        Some(terms) => terms,
+        None => {
+            host::throw_error(scope, &format!("invalid dice notation: {}", notation));
+            return;
+        }
+    };
+    let state = scope.get_slot::<State>().unwrap().clone();
+    let mut rolls = Vec::new();
+    let mut total = 0;
+    for term in terms {
+        match term {
+            Term::Dice { count, sides, sign } => {
+                for _ in 0..count {
+                    let roll = state.borrow_mut().below(sides) as i64 + 1;
+                    rolls.push(roll);
+                    total += sign * roll;
+                }
+            }
+            Term::Constant(k) => total += k,
+        }
+    }
+    let result = json!({ "total": total, "rolls": rolls });
+    state.borrow_mut().record(json!({ "call": "dice", "notation": notation, "result": result }));
+    if let Some(result) = host::from_json(scope, &result) {
+        rv.set(result);
+    }
+}
+
+pub fn install(scope: &mut rusty_v8::HandleScope, random: Random) {
+    let object = rusty_v8::Object::new(scope);
+    host::set_function(scope, object, "pick", random_pick);
+    host::set_function(scope, object, "shuffle", random_shuffle);
+    host::set_function(scope, object, "dice", random_dice);
+    host::set_global(scope, "random", object.into());
+    scope.set_slot::<State>(Rc::new(RefCell::new(random)));
+}
+
+pub fn take(isolate: &mut rusty_v8::Isolate) -> Option<Random> {
+    isolate
+        .remove_slot::<State>()
+        .and_then(|state| Rc::try_unwrap(state).ok())
+        .map(RefCell::into_inner)
+}
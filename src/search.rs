@@ -0,0 +1,160 @@
+use crate::host;
+use serde_json::{json, Value};
+use unicode_normalization::UnicodeNormalization;
+
+const DEFAULT_LIMIT: u64 = 10;
+const DEFAULT_THRESHOLD: f64 = 0.3;
+const MAX_CELLS: usize = 10_000_000;
+
+fn fold(text: &str) -> Vec<char> {
+    text.nfkc().flat_map(char::to_lowercase).collect()
+}
+
+fn distance(a: &[char], b: &[char]) -> usize {
+    let mut before: Vec<usize> = Vec::new();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut row = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        row[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            row[j] = (prev[j] + 1).min(row[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut prev, std::mem::replace(&mut row, before));
+        row.resize(b.len() + 1, 0);
+    }
+    prev[b.len()]
+}
+
+struct Budget(usize);
+
+impl Budget {
+    fn spend(&mut self, scope: &mut rusty_v8::HandleScope, a: usize, b: usize) -> bool {
+        match self.0.checked_sub(a.max(1) * b.max(1)) {
+            Some(left) => {
+                self.0 = left;
+                true
+            }
+            None => {
+                host::throw_range_error(scope, &format!("search input too large: at most {} character comparisons per call", MAX_CELLS));
+                false
+            }
+        }
+    }
+}
+
+fn contains(haystack: &[char], needle: &[char]) -> bool {
+    needle.is_empty() || haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+fn score(query: &[char], item: &[char]) -> f64 {
+    let longest = query.len().max(item.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    if contains(item, query) {
+        return 0.5 + 0.5 * query.len() as f64 / item.len() as f64;
+    }
+    0.5 * (1.0 - distance(query, item) as f64 / longest as f64)
+}
+
+fn items_arg(scope: &mut rusty_v8::HandleScope, args: &rusty_v8::FunctionCallbackArguments) -> Option<Vec<String>> {
+    let items = host::to_json(scope, args.get(1)).and_then(|items| match items {
+        Value::Array(items) => items.into_iter().map(|i| i.as_str().map(str::to_string)).collect(),
+        _ => None,
+    });
+    if items.is_none() {
+        host::throw_type_error(scope, "items must be an array of strings");
+    }
+    items
+}
+
+fn search_distance(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let a = match host::string_arg(scope, &args, 0, "a") {
+        Some(a) => a,
+        None => return,
+    };
+    let b = match host::string_arg(scope, &args, 1, "b") {
+        Some(b) => b,
+        None => return,
+    };
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if Budget(MAX_CELLS).spend(scope, a.len(), b.len()) {
+        rv.set(rusty_v8::Number::new(scope, distance(&a, &b) as f64).into());
+    }
+}
+
+fn search_find(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let query = match host::string_arg(scope, &args, 0, "query") {
+        Some(query) => fold(&query),
+        None => return,
+    };
+    let items = match items_arg(scope, &args) {
+        Some(items) => items,
+        None => return,
+    };
+    let mut budget = Budget(MAX_CELLS);
+    let mut found = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let item = fold(item);
+        if !budget.spend(scope, query.len(), item.len()) {
+            return;
+        }
+        if contains(&item, &query) {
+            found.push(json!(i));
+        }
+    }
+    if let Some(found) = host::from_json(scope, &Value::Array(found)) {
+        rv.set(found);
+    }
+}
+
+fn search_fuzzy(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let query = match host::string_arg(scope, &args, 0, "query") {
+        Some(query) => fold(&query),
+        None => return,
+    };
+    let items = match items_arg(scope, &args) {
+        Some(items) => items,
+        None => return,
+    };
+    let options = match host::options_arg(scope, &args, 2) {
+        Some(options) => options,
+        None => return,
+    };
+    let limit = options["limit"].as_u64().unwrap_or(DEFAULT_LIMIT) as usize;
+    let threshold = options["threshold"].as_f64().unwrap_or(DEFAULT_THRESHOLD);
+    let mut budget = Budget(MAX_CELLS);
+    let mut matches: Vec<(usize, f64)> = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let item = fold(item);
+        if !budget.spend(scope, query.len(), item.len()) {
+            return;
+        }
+        let score = score(&query, &item);
+        if score >= threshold {
+            matches.push((i, score));
+        }
+    }
+    matches.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    let matches: Vec<Value> = matches
+        .into_iter()
+        .take(limit)
+        .map(|(i, score)| json!({ "item": items[i], "index": i, "score": score }))
+        .collect();
+    if let Some(matches) = host::from_json(scope, &Value::Array(matches)) {
+        rv.set(matches);
+    }
+}
+
+pub fn install(scope: &mut rusty_v8::HandleScope) {
+    let object = rusty_v8::Object::new(scope);
+    host::set_function(scope, object, "distance", search_distance);
+    host::set_function(scope, object, "find", search_find);
+    host::set_function(scope, object, "fuzzy", search_fuzzy);
+    host::set_global(scope, "search", object.into());
+}
@@ -0,0 +1,305 @@
+use crate::protocol::ResultFormat;
+use serde_json::{json, Map, Value};
+use std::convert::TryFrom;
+
+const MAX_DEPTH: usize = 64;
+const MAX_CAUSE_DEPTH: usize = 8;
+
+pub enum SerializeError {
+    TooLarge,
+    Exception,
+}
+
+struct Encoder<'a, 's> {
+    budget: usize,
+    stack: Vec<(rusty_v8::Local<'s, rusty_v8::Value>, String)>,
+    scope: &'a mut rusty_v8::HandleScope<'s>,
+}
+
+fn tagged(tag: &str, key: &str, value: Value) -> Value {
+    let mut map = Map::new();
+    map.insert("$type".to_string(), Value::String(tag.to_string()));
+    map.insert(key.to_string(), value);
+    Value::Object(map)
+}
+
+fn days_to_civil(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+pub fn iso_date(ms: f64) -> Option<String> {
+    if !ms.is_finite() {
+        return None;
+    }
+    let ms = ms as i64;
+    let (days, rem) = (ms.div_euclid(86_400_000), ms.rem_euclid(86_400_000));
+    let (y, m, d) = days_to_civil(days);
+    let year = if (0..=9999).contains(&y) { format!("{:04}", y) } else { format!("{:+07}", y) };
+    Some(format!(
+        "{}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        m,
+        d,
+        rem / 3_600_000,
+        rem / 60_000 % 60,
+        rem / 1000 % 60,
+        rem % 1000
+    ))
+}
+
+impl<'a, 's> Encoder<'a, 's> {
+    fn spend(&mut self, bytes: usize) -> Result<(), SerializeError> {
+        self.budget = self.budget.checked_sub(bytes).ok_or(SerializeError::TooLarge)?;
+        Ok(())
+    }
+
+    fn string(&mut self, value: rusty_v8::Local<rusty_v8::Value>) -> Result<String, SerializeError> {
+        let s = value.to_string(self.scope).ok_or(SerializeError::Exception)?;
+        let s = s.to_rust_string_lossy(self.scope);
+        self.spend(s.len() + 2)?;
+        Ok(s)
+    }
+
+    fn array_from(&mut self, value: rusty_v8::Local<'s, rusty_v8::Value>) -> Result<rusty_v8::Local<'s, rusty_v8::Array>, SerializeError> {
+        let global = self.scope.get_current_context().global(self.scope);
+        let array_key = rusty_v8::String::new(self.scope, "Array").unwrap();
+        let from_key = rusty_v8::String::new(self.scope, "from").unwrap();
+        let array = global.get(self.scope, array_key.into()).ok_or(SerializeError::Exception)?;
+        let array = rusty_v8::Local::<rusty_v8::Object>::try_from(array).map_err(|_| SerializeError::Exception)?;
+        let from = array.get(self.scope, from_key.into()).ok_or(SerializeError::Exception)?;
+        let from = rusty_v8::Local::<rusty_v8::Function>::try_from(from).map_err(|_| SerializeError::Exception)?;
+        let result = from.call(self.scope, array.into(), &[value]).ok_or(SerializeError::Exception)?;
+        rusty_v8::Local::<rusty_v8::Array>::try_from(result).map_err(|_| SerializeError::Exception)
+    }
+
+    fn elements(&mut self, array: rusty_v8::Local<'s, rusty_v8::Array>, path: &str) -> Result<Vec<Value>, SerializeError> {
+        let mut out = Vec::with_capacity((array.length() as usize).min(self.budget));
+        for i in 0..array.length() {
+            let item = array.get_index(self.scope, i).ok_or(SerializeError::Exception)?;
+            out.push(self.encode(item, &format!("{}[{}]", path, i))?);
+            self.spend(1)?;
+        }
+        Ok(out)
+    }
+
+    fn number(&mut self, n: f64) -> Result<Value, SerializeError> {
+        self.spend(24)?;
+        if n == 0.0 && n.is_sign_negative() {
+            return Ok(tagged("number", "value", Value::String("-0".to_string())));
+        }
+        if n.fract() == 0.0 && n.abs() <= 9_007_199_254_740_991.0 {
+            return Ok(Value::Number((n as i64).into()));
+        }
+        Ok(match serde_json::Number::from_f64(n) {
+            Some(n) => Value::Number(n),
+            None if n.is_nan() => tagged("number", "value", Value::String("NaN".to_string())),
+            None if n > 0.0 => tagged("number", "value", Value::String("Infinity".to_string())),
+            None => tagged("number", "value", Value::String("-Infinity".to_string())),
+        })
+    }
+
+    fn encode(&mut self, value: rusty_v8::Local<'s, rusty_v8::Value>, path: &str) -> Result<Value, SerializeError> {
+        if value.is_null() {
+            self.spend(4)?;
+            return Ok(Value::Null);
+        }
+        if value.is_undefined() {
+            self.spend(24)?;
+            return Ok(json!({ "$type": "undefined" }));
+        }
+        if value.is_boolean() {
+            self.spend(5)?;
+            return Ok(Value::Bool(value.boolean_value(self.scope)));
+        }
+        if value.is_number() {
+            let n = value.number_value(self.scope).unwrap_or(f64::NAN);
+            return self.number(n);
+        }
+        if value.is_string() {
+            return Ok(Value::String(self.string(value)?));
+        }
+        if value.is_big_int() {
+            let s = self.string(value)?;
+            return Ok(tagged("bigint", "value", Value::String(s)));
+        }
+        if value.is_symbol() {
+            let symbol = rusty_v8::Local::<rusty_v8::Symbol>::try_from(value).map_err(|_| SerializeError::Exception)?;
+            let description = symbol.description(self.scope);
+            let description = if description.is_undefined() { Value::Null } else { Value::String(self.string(description)?) };
+            return Ok(tagged("symbol", "description", description));
+        }
+        if let Some((_, target)) = self.stack.iter().find(|(v, _)| v.strict_equals(value)) {
+            let target = target.clone();
+            self.spend(target.len() + 24)?;
+            return Ok(tagged("ref", "path", Value::String(target)));
+        }
+        if self.stack.len() >= MAX_DEPTH {
+            self.spend(24)?;
+            return Ok(json!({ "$type": "truncated" }));
+        }
+        self.stack.push((value, path.to_string()));
+        let encoded = self.encode_object(value, path);
+        self.stack.pop();
+        encoded
+    }
+
+    fn encode_object(&mut self, value: rusty_v8::Local<'s, rusty_v8::Value>, path: &str) -> Result<Value, SerializeError> {
+        if value.is_function() {
+            let function = rusty_v8::Local::<rusty_v8::Function>::try_from(value).map_err(|_| SerializeError::Exception)?;
+            let name = function.get_name(self.scope);
+            let name = self.string(name.into())?;
+            return Ok(tagged("function", "name", Value::String(name)));
+        }
+        if value.is_date() {
+            let date = rusty_v8::Local::<rusty_v8::Date>::try_from(value).map_err(|_| SerializeError::Exception)?;
+            self.spend(40)?;
+            return Ok(tagged("date", "value", iso_date(date.value_of()).map_or(Value::Null, Value::String)));
+        }
+        if value.is_reg_exp() {
+            let s = self.string(value)?;
+            return Ok(tagged("regexp", "value", Value::String(s)));
+        }
+        if value.is_native_error() {
+            let s = self.string(value)?;
+            return Ok(tagged("error", "value", Value::String(s)));
+        }
+        if value.is_promise() {
+            self.spend(24)?;
+            return Ok(json!({ "$type": "promise" }));
+        }
+        if value.is_map() {
+            let map = rusty_v8::Local::<rusty_v8::Map>::try_from(value).map_err(|_| SerializeError::Exception)?;
+            let flat = map.as_array(self.scope);
+            let mut entries = Vec::with_capacity(flat.length() as usize / 2);
+            for i in 0..flat.length() / 2 {
+                let k = flat.get_index(self.scope, 2 * i).ok_or(SerializeError::Exception)?;
+                let v = flat.get_index(self.scope, 2 * i + 1).ok_or(SerializeError::Exception)?;
+                let k = self.encode(k, &format!("{}.entries[{}][0]", path, i))?;
+                let v = self.encode(v, &format!("{}.entries[{}][1]", path, i))?;
+                entries.push(Value::Array(vec![k, v]));
+            }
+            return Ok(tagged("map", "entries", Value::Array(entries)));
+        }
+        if value.is_set() {
+            let values = self.array_from(value)?;
+            let values = self.elements(values, &format!("{}.values", path))?;
+            return Ok(tagged("set", "values", Value::Array(values)));
+        }
+        if value.is_array() {
+            let array = rusty_v8::Local::<rusty_v8::Array>::try_from(value).map_err(|_| SerializeError::Exception)?;
+            return Ok(Value::Array(self.elements(array, path)?));
+        }
+        let object = rusty_v8::Local::<rusty_v8::Object>::try_from(value).map_err(|_| SerializeError::Exception)?;
+        let keys = object.get_own_property_names(self.scope).ok_or(SerializeError::Exception)?;
+        let mut map = Map::new();
+        for i in 0..keys.length() {
+            let key = keys.get_index(self.scope, i).ok_or(SerializeError::Exception)?;
+            let name = self.string(key)?;
+            let item = object.get(self.scope, key).ok_or(SerializeError::Exception)?;
+            let item = self.encode(item, &format!("{}.{}", path, name))?;
+            map.insert(name, item);
+        }
+        if map.contains_key("$type") {
+            return Ok(tagged("object", "value", Value::Object(map)));
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+impl<'a, 's> Encoder<'a, 's> {
+    fn property(&mut self, object: rusty_v8::Local<'s, rusty_v8::Object>, name: &str) -> Option<rusty_v8::Local<'s, rusty_v8::Value>> {
+        let key = rusty_v8::String::new(self.scope, name)?;
+        object.get(self.scope, key.into())
+    }
+
+    fn error(&mut self, value: rusty_v8::Local<'s, rusty_v8::Value>, path: &str) -> Result<Value, SerializeError> {
+        if !value.is_native_error() || self.stack.len() >= MAX_CAUSE_DEPTH {
+            let value = self.encode(value, path)?;
+            return Ok(json!({ "value": value }));
+        }
+        let object = rusty_v8::Local::<rusty_v8::Object>::try_from(value).map_err(|_| SerializeError::Exception)?;
+        let mut detail = Map::new();
+        for field in &["name", "message", "stack"] {
+            if let Some(v) = self.property(object, field).filter(|v| v.is_string()) {
+                detail.insert(field.to_string(), Value::String(self.string(v)?));
+            }
+        }
+        self.stack.push((value, path.to_string()));
+        let cause_key = rusty_v8::String::new(self.scope, "cause").unwrap();
+        if object.has(self.scope, cause_key.into()).unwrap_or(false) {
+            let cause = object.get(self.scope, cause_key.into()).ok_or(SerializeError::Exception)?;
+            let cause_path = format!("{}.cause", path);
+            let cause = match self.stack.iter().find(|(v, _)| v.strict_equals(cause)) {
+                Some((_, target)) => tagged("ref", "path", Value::String(target.clone())),
+                None => self.error(cause, &cause_path)?,
+            };
+            detail.insert("cause".to_string(), cause);
+        }
+        let keys = object.get_own_property_names(self.scope).ok_or(SerializeError::Exception)?;
+        let mut props = Map::new();
+        for i in 0..keys.length() {
+            let key = keys.get_index(self.scope, i).ok_or(SerializeError::Exception)?;
+            let name = self.string(key)?;
+            if name == "cause" {
+                continue;
+            }
+            let item = object.get(self.scope, key).ok_or(SerializeError::Exception)?;
+            let item = self.encode(item, &format!("{}.props.{}", path, name))?;
+            props.insert(name, item);
+        }
+        self.stack.pop();
+        if !props.is_empty() {
+            detail.insert("props".to_string(), Value::Object(props));
+        }
+        Ok(Value::Object(detail))
+    }
+}
+
+pub fn encode_error<'s>(
+    scope: &mut rusty_v8::HandleScope<'s>,
+    exception: rusty_v8::Local<'s, rusty_v8::Value>,
+    max_bytes: usize,
+) -> Option<Value> {
+    let scope = &mut rusty_v8::TryCatch::new(scope);
+    let mut encoder = Encoder {
+        budget: max_bytes,
+        stack: Vec::new(),
+        scope,
+    };
+    encoder.error(exception, "$").ok()
+}
+
+pub fn encode<'s>(
+    scope: &mut rusty_v8::HandleScope<'s>,
+    value: rusty_v8::Local<'s, rusty_v8::Value>,
+    format: ResultFormat,
+    max_bytes: usize,
+) -> Result<Value, SerializeError> {
+    match format {
+        ResultFormat::String => {
+            let s = value.to_string(scope).ok_or(SerializeError::Exception)?;
+            let s = s.to_rust_string_lossy(scope);
+            if s.len() > max_bytes {
+                return Err(SerializeError::TooLarge);
+            }
+            Ok(Value::String(s))
+        }
+        ResultFormat::Extended => {
+            let mut encoder = Encoder {
+                budget: max_bytes,
+                stack: Vec::new(),
+                scope,
+            };
+            encoder.encode(value, "$")
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::host;
+use serde_json::Value;
+use std::convert::TryFrom;
+
+const TRACER: &str = r#"(max) => {
+    const calls = [];
+    let dropped = 0;
+    const { stringify, parse } = JSON;
+    const snapshot = (value) => {
+        try {
+            const json = stringify(value);
+            return json === undefined ? null : parse(json);
+        } catch (e) {
+            return "[unserializable]";
+        }
+    };
+    const wrap = (name, object) => {
+        for (const key of Object.keys(object)) {
+            const f = object[key];
+            if (typeof f !== "function") continue;
+            object[key] = function (...args) {
+                const call = { call: name + "." + key, args: snapshot(args) };
+                try {
+                    const result = f.apply(this, args);
+                    call.result = snapshot(result);
+                    return result;
+                } catch (e) {
+                    call.error = String(e);
+                    throw e;
+                } finally {
+                    if (calls.length < max) calls.push(call);
+                    else dropped++;
+                }
+            };
+        }
+    };
+    for (const name of ["calc", "chart", "console", "data", "format", "fs", "random", "search"]) {
+        const object = Object.getOwnPropertyDescriptor(globalThis, name);
+        if (object && object.value) wrap(name, object.value);
+    }
+    return () => ({ calls, dropped });
+}"#;
+
+struct HostCalls(Value);
+
+pub struct Tracer(rusty_v8::Global<rusty_v8::Function>);
+
+impl Tracer {
+    pub fn install(scope: &mut rusty_v8::HandleScope, max_calls: usize) -> Option<Tracer> {
+        let source = rusty_v8::String::new(scope, TRACER).unwrap();
+        let factory = rusty_v8::Script::compile(scope, source, None)?.run(scope)?;
+        let factory = rusty_v8::Local::<rusty_v8::Function>::try_from(factory).ok()?;
+        let recv = rusty_v8::undefined(scope).into();
+        let max = rusty_v8::Number::new(scope, max_calls as f64).into();
+        let collect = factory.call(scope, recv, &[max])?;
+        let collect = rusty_v8::Local::<rusty_v8::Function>::try_from(collect).ok()?;
+        Some(Tracer(rusty_v8::Global::new(scope, collect)))
+    }
+
+    pub fn finish(self, scope: &mut rusty_v8::HandleScope) {
+        let collect = rusty_v8::Local::new(scope, &self.0);
+        let recv = rusty_v8::undefined(scope).into();
+        let traced = collect.call(scope, recv, &[]).and_then(|calls| host::to_json(scope, calls));
+        if let Some(traced) = traced {
+            scope.set_slot(HostCalls(traced));
+        }
+    }
+}
+
+pub fn take(isolate: &mut rusty_v8::Isolate) -> Option<Value> {
+    isolate.remove_slot::<HostCalls>().map(|c| c.0)
+}
@@ -0,0 +1,405 @@
+use crate::config::Config;
+use crate::console::{self, Console};
+use crate::datasets::{self, Datasets};
+use crate::engine::{calls_result, effective_timeout, elapsed_ms, unknown_entry, Cancel, Capabilities, Engine, HANDLERS};
+use crate::protocol::{self, ErrorCode, ExecError, Input, ScriptResult, Stats};
+use crate::serialize::{self, SerializeError};
+use crate::vfs::{self, Vfs};
+use crate::random::{self, Random};
+use crate::{calc, capability, ctx, data, format, host, profiler, search, trace};
+use serde_json::Value;
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Once};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+fn script_error(scope: &mut rusty_v8::TryCatch<rusty_v8::HandleScope>, config: &Config, code: ErrorCode) -> ExecError {
+    if let Some(exp) = scope.exception() {
+        let message = rusty_v8::Exception::create_message(scope, exp).get(scope).to_rust_string_lossy(scope);
+        let mut err = ExecError::new(code, message);
+        err.detail = serialize::encode_error(scope, exp, config.max_result_bytes);
+        err
+    } else {
+        ExecError::new(code, "")
+    }
+}
+
+fn global_function<'s>(scope: &mut rusty_v8::HandleScope<'s>, name: &str) -> Option<rusty_v8::Local<'s, rusty_v8::Function>> {
+    let global = scope.get_current_context().global(scope);
+    let key = rusty_v8::String::new(scope, name).unwrap();
+    global.get(scope, key.into()).and_then(|f| rusty_v8::Local::<rusty_v8::Function>::try_from(f).ok())
+}
+
+fn call_entry<'s>(
+    scope: &mut rusty_v8::TryCatch<rusty_v8::HandleScope<'s>>,
+    config: &Config,
+    entry: Option<&str>,
+    args: &[Value],
+    completion: rusty_v8::Local<'s, rusty_v8::Value>,
+) -> Result<rusty_v8::Local<'s, rusty_v8::Value>, ExecError> {
+    let handler = match entry {
+        Some(entry) => Some(global_function(scope, entry).ok_or_else(|| unknown_entry(entry))?),
+        None => HANDLERS.iter().find_map(|name| global_function(scope, name)),
+    };
+    let handler = match handler {
+        Some(handler) => handler,
+        None => return Ok(completion),
+    };
+    let mut values = Vec::with_capacity(args.len());
+    for arg in args {
+        let text = rusty_v8::String::new(scope, &arg.to_string()).unwrap();
+        let value = rusty_v8::json::parse(scope, text).ok_or_else(|| script_error(scope, config, ErrorCode::Exception))?;
+        values.push(value);
+    }
+    let recv = rusty_v8::undefined(scope).into();
+    handler.call(scope, recv, &values).ok_or_else(|| script_error(scope, config, ErrorCode::Exception))
+}
+
+fn run_calls<'s>(
+    scope: &mut rusty_v8::TryCatch<rusty_v8::HandleScope<'s>>,
+    input: &Input,
+    config: &Config,
+    completion: rusty_v8::Local<'s, rusty_v8::Value>,
+    stats: &mut Stats,
+) -> Result<Value, ExecError> {
+    let mut results = Vec::with_capacity(input.calls.len());
+    for call in &input.calls {
+        let val = call_entry(scope, config, Some(&call.entry), &call.args, completion)?;
+        results.push(encode_result(scope, input, config, val, stats)?);
+    }
+    calls_result(results, config.max_result_bytes)
+}
+
+fn encode_result<'s>(
+    scope: &mut rusty_v8::TryCatch<rusty_v8::HandleScope<'s>>,
+    input: &Input,
+    config: &Config,
+    val: rusty_v8::Local<'s, rusty_v8::Value>,
+    stats: &mut Stats,
+) -> Result<Value, ExecError> {
+    let started = Instant::now();
+    let encoded = serialize::encode(scope, val, input.result_format, config.max_result_bytes);
+    stats.serialize_ms += elapsed_ms(started);
+    match encoded {
+        Ok(result) => Ok(result),
+        Err(SerializeError::TooLarge) => Err(ExecError::new(ErrorCode::ResultTooLarge, "Result too large")),
+        Err(SerializeError::Exception) => Err(script_error(scope, config, ErrorCode::Exception)),
+    }
+}
+
+struct HeapGuard {
+    handle: rusty_v8::IsolateHandle,
+    hard_limit: usize,
+    soft_hit: Cell<bool>,
+    exceeded: Cell<bool>,
+}
+
+extern "C" fn near_heap_limit(data: *mut c_void, current_heap_limit: usize, _initial_heap_limit: usize) -> usize {
+    let guard = unsafe { &*(data as *const HeapGuard) };
+    if current_heap_limit < guard.hard_limit {
+        warn!("soft heap limit of {} bytes reached", current_heap_limit);
+        guard.soft_hit.set(true);
+        return guard.hard_limit;
+    }
+    guard.exceeded.set(true);
+    guard.handle.terminate_execution();
+    current_heap_limit * 2
+}
+
+struct Watchdog {
+    done: mpsc::Sender<()>,
+    thread: JoinHandle<()>,
+    fired: Arc<AtomicBool>,
+}
+
+impl Watchdog {
+    fn start(handle: rusty_v8::IsolateHandle, timeout: Duration) -> Watchdog {
+        let (done, rx) = mpsc::channel();
+        let fired = Arc::new(AtomicBool::new(false));
+        let flag = fired.clone();
+        let thread = std::thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = rx.recv_timeout(timeout) {
+                flag.store(true, Ordering::SeqCst);
+                handle.terminate_execution();
+            }
+        });
+        Watchdog { done, thread, fired }
+    }
+
+    fn stop(self) -> bool {
+        let _ = self.done.send(());
+        let _ = self.thread.join();
+        self.fired.load(Ordering::SeqCst)
+    }
+}
+
+fn run_script(isolate: &mut rusty_v8::Isolate, input: &Input, config: &Config, console: Console, datasets: Datasets, deadline: Option<Instant>, stats: &mut Stats) -> Result<Value, ExecError> {
+    let base_scope = &mut rusty_v8::HandleScope::new(isolate);
+    let context = rusty_v8::Context::new(base_scope);
+    let context_scope = &mut rusty_v8::ContextScope::new(base_scope, context);
+    capability::install(context_scope);
+    if config.api_enabled("calc") {
+        calc::install(context_scope);
+    } else {
+        capability::disable(context_scope, "calc");
+    }
+    #[cfg(feature = "chart")]
+    if config.api_enabled("chart") {
+        crate::chart::install(context_scope);
+    } else {
+        capability::disable(context_scope, "chart");
+    }
+    if config.api_enabled("console") {
+        console::install(context_scope, console);
+    } else {
+        capability::disable(context_scope, "console");
+    }
+    if config.api_enabled("data") {
+        data::install(context_scope, config.max_parse_bytes);
+    } else {
+        capability::disable(context_scope, "data");
+    }
+    if config.api_enabled("datasets") {
+        datasets::install(context_scope, datasets);
+    } else {
+        capability::disable(context_scope, "datasets");
+    }
+    if config.api_enabled("format") {
+        format::install(context_scope);
+    } else {
+        capability::disable(context_scope, "format");
+    }
+    if config.api_enabled("fs") {
+        vfs::install(context_scope, Vfs::new(config.fs_quota_bytes));
+    } else {
+        capability::disable(context_scope, "fs");
+    }
+    if config.api_enabled("random") {
+        random::install(context_scope, Random::new(input.random_seed, config.max_log_lines, config.max_log_bytes));
+    } else {
+        capability::disable(context_scope, "random");
+    }
+    if config.api_enabled("search") {
+        search::install(context_scope);
+    } else {
+        capability::disable(context_scope, "search");
+    }
+    stats.apis = config.enabled_apis();
+    let quota = ctx::Quota { deadline, max_heap_bytes: config.max_heap_bytes };
+    ctx::install(context_scope, &input.context(), quota);
+    let tracer = if input.trace_host_calls {
+        trace::Tracer::install(context_scope, config.max_log_lines)
+    } else {
+        None
+    };
+    let profiler = if input.profile_alloc {
+        Some(profiler::AllocProfiler::start(context_scope, context))
+    } else {
+        None
+    };
+    let scope = &mut rusty_v8::TryCatch::new(context_scope);
+    let (source, line_offset, column_offset) = match &config.wrapper {
+        Some(wrapper) => (wrapper.apply(&input.script), wrapper.line_offset(), wrapper.column_offset()),
+        None => (input.script.clone(), 0, 0),
+    };
+    let code = rusty_v8::String::new(scope, &source).unwrap();
+    code.to_rust_string_lossy(scope);
+    let name = rusty_v8::undefined(scope).into();
+    let source_map_url = rusty_v8::undefined(scope).into();
+    let origin = rusty_v8::ScriptOrigin::new(scope, name, line_offset, column_offset, false, 0, source_map_url, false, false, false);
+    let started = Instant::now();
+    let script = rusty_v8::Script::compile(scope, code, Some(&origin));
+    stats.compile_ms = elapsed_ms(started);
+    let result = if let Some(script) = script {
+        let started = Instant::now();
+        let result = match script.run(scope) {
+            Some(val) if input.calls.is_empty() => {
+                call_entry(scope, config, input.entry.as_deref(), &input.args, val).and_then(|val| encode_result(scope, input, config, val, stats))
+            }
+            Some(val) => run_calls(scope, input, config, val, stats),
+            None => Err(script_error(scope, config, ErrorCode::Exception)),
+        };
+        stats.exec_ms = elapsed_ms(started) - stats.serialize_ms;
+        result
+    } else {
+        Err(script_error(scope, config, ErrorCode::Syntax))
+    };
+    if let Some(tracer) = tracer {
+        tracer.finish(scope);
+    }
+    if let Some(profiler) = profiler {
+        profiler.finish(scope);
+    }
+    result
+}
+
+pub struct V8Engine;
+
+impl Engine for V8Engine {
+    fn name(&self) -> &'static str {
+        "v8"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            calc: true,
+            chart: cfg!(feature = "chart"),
+            console: true,
+            ctx: true,
+            data: true,
+            datasets: true,
+            format: true,
+            random: true,
+            search: true,
+            extended_results: true,
+            alloc_profile: true,
+            host_trace: true,
+            fs: true,
+            heap_limit: true,
+            timeout: true,
+        }
+    }
+
+    fn exec(&self, input: &Input, config: &Config, cancel: Option<&Cancel>) -> ScriptResult {
+        exec_v8_with(input, config, cancel)
+    }
+}
+
+pub fn exec_v8(input: &Input, config: &Config) -> ScriptResult {
+    exec_v8_with(input, config, None)
+}
+
+pub fn exec_v8_with(input: &Input, config: &Config, cancel: Option<&Cancel>) -> ScriptResult {
+    if cancel.is_some_and(Cancel::is_cancelled) {
+        return ScriptResult::err(ErrorCode::Cancelled, "Cancelled");
+    }
+    let mut stats = Stats {
+        queued_ms: input.queued_at.map_or(0.0, elapsed_ms),
+        ..Stats::default()
+    };
+    let timeout = match effective_timeout(config.timeout, input.deadline_unix_ms) {
+        Ok(timeout) => timeout,
+        Err(e) => return ScriptResult::from(e),
+    };
+    let datasets = match Datasets::resolve(&config.datasets, &input.datasets) {
+        Ok(datasets) => datasets,
+        Err(e) => return ScriptResult::from(e),
+    };
+    let mut params = rusty_v8::CreateParams::default();
+    if let Some(max) = config.max_heap_bytes {
+        params = params.heap_limits(0, config.soft_heap_bytes.unwrap_or(max));
+    }
+    let mut isolate = rusty_v8::Isolate::new(params);
+    let guard = Box::new(HeapGuard {
+        handle: isolate.thread_safe_handle(),
+        hard_limit: config.max_heap_bytes.unwrap_or(0),
+        soft_hit: Cell::new(false),
+        exceeded: Cell::new(false)
+    });
+    if config.max_heap_bytes.is_some() {
+        isolate.add_near_heap_limit_callback(near_heap_limit, &*guard as *const HeapGuard as *mut c_void);
+    }
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let watchdog = timeout.map(|t| Watchdog::start(isolate.thread_safe_handle(), t));
+    if let Some(cancel) = cancel {
+        let handle = isolate.thread_safe_handle();
+        cancel.attach(Box::new(move || {
+            handle.terminate_execution();
+        }));
+    }
+    let console = Console::new(config.max_log_lines, config.max_log_bytes, Instant::now());
+    let result = run_script(&mut isolate, input, config, console, datasets, deadline, &mut stats);
+    let timed_out = watchdog.is_some_and(Watchdog::stop);
+    if let Some(cancel) = cancel {
+        cancel.detach();
+    }
+    let console = console::take(&mut isolate);
+    let datasets = datasets::take(&mut isolate);
+    let alloc_profile = profiler::take(&mut isolate);
+    let host_calls = trace::take(&mut isolate);
+    let vfs = vfs::take(&mut isolate);
+    let random = random::take(&mut isolate);
+    let panicked = host::panicked(&mut isolate);
+    let mut heap = rusty_v8::HeapStatistics::default();
+    isolate.get_heap_statistics(&mut heap);
+    drop(isolate);
+    let mut res = ScriptResult::from(match result {
+        _ if panicked => Err(ExecError::new(ErrorCode::Internal, "Internal error")),
+        Err(_) if guard.exceeded.get() => Err(ExecError::new(ErrorCode::Oom, "Memory limit")),
+        Err(_) if timed_out => Err(ExecError::new(ErrorCode::Timeout, "Timeout")),
+        Err(_) if cancel.is_some_and(Cancel::is_cancelled) => Err(ExecError::new(ErrorCode::Cancelled, "Cancelled")),
+        result => result,
+    });
+    if let Some(console) = console {
+        res.logs = console.output;
+        res.logs_truncated = console.truncated;
+    }
+    res.stats = stats;
+    res.stats.datasets_accessed = datasets.accessed;
+    res.stats.dataset_paths = datasets.access_paths.into_iter().collect();
+    res.stats.dataset_paths_truncated = datasets.access_paths_truncated;
+    res.stats.soft_heap_limit_hit = guard.soft_hit.get();
+    res.stats.heap_bytes = heap.total_heap_size();
+    res.stats.random = random.and_then(|random| random.stats());
+    if let Some((sites, truncated)) = alloc_profile {
+        res.alloc_profile = Some(sites);
+        res.alloc_profile_truncated = truncated;
+    }
+    res.host_calls = host_calls;
+    if input.return_files {
+        res.files = vfs.map(|vfs| vfs.files);
+    }
+    res
+}
+
+static INIT: Once = Once::new();
+
+pub fn initialize(v8_flags: &[String]) -> Result<(), String> {
+    if !v8_flags.is_empty() {
+        debug!("v8 flags: {}", v8_flags.join(" "));
+        let mut args = vec!["bot_script_runner".to_string()];
+        args.extend(v8_flags.iter().cloned());
+        let rest = rusty_v8::V8::set_flags_from_command_line(args);
+        if rest.len() > 1 {
+            return Err(format!("unrecognized V8 flags: {}", rest[1..].join(" ")));
+        }
+    }
+    INIT.call_once(|| {
+        let platform = rusty_v8::new_default_platform(0, false).make_shared();
+        rusty_v8::V8::initialize_platform(platform);
+        rusty_v8::V8::initialize();
+    });
+    Ok(())
+}
+
+fn fuzz_config() -> Config {
+    Config {
+        max_heap_bytes: Some(16 * 1024 * 1024),
+        timeout: Some(Duration::from_millis(200)),
+        max_log_lines: 16,
+        max_log_bytes: 1024,
+        max_result_bytes: 4096,
+        max_script_bytes: 4096,
+        ..Config::default()
+    }
+}
+
+pub fn fuzz_exec(input: &[u8]) {
+    let config = fuzz_config();
+    initialize(&[]).unwrap();
+    let input = match protocol::read_input(input, config.max_script_bytes) {
+        Ok(input) => input,
+        Err(_) => {
+            let script = String::from_utf8_lossy(&input[..input.len().min(config.max_script_bytes)]);
+            match serde_json::from_value(serde_json::json!({ "script": script })) {
+                Ok(input) => input,
+                Err(_) => return,
+            }
+        }
+    };
+    let res = exec_v8(&input, &config);
+    serde_json::to_string(&res).unwrap();
+}
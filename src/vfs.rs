@@ -0,0 +1,156 @@
+use crate::host;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+pub struct Vfs {
+    pub files: BTreeMap<String, String>,
+    bytes: usize,
+    quota: usize,
+}
+
+type State = Rc<RefCell<Vfs>>;
+
+impl Vfs {
+    pub fn new(quota: usize) -> Vfs {
+        Vfs { files: BTreeMap::new(), bytes: 0, quota }
+    }
+
+    fn write(&mut self, path: String, data: String) -> Result<(), String> {
+        let old = self.files.get(&path).map_or(0, |f| path.len() + f.len());
+        let bytes = self.bytes - old + path.len() + data.len();
+        if bytes > self.quota {
+            return Err(format!("ENOSPC: quota of {} bytes exceeded", self.quota));
+        }
+        self.bytes = bytes;
+        self.files.insert(path, data);
+        Ok(())
+    }
+}
+
+fn normalize(path: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(format!("/{}", parts.join("/")))
+}
+
+fn path_arg(scope: &mut rusty_v8::HandleScope, args: &rusty_v8::FunctionCallbackArguments) -> Option<String> {
+    let path = host::string_arg(scope, args, 0, "path")?;
+    let normalized = normalize(&path);
+    if normalized.is_none() {
+        host::throw_error(scope, &format!("EINVAL: invalid path: {}", path));
+    }
+    normalized
+}
+
+fn fs_read_file(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let path = match path_arg(scope, &args) {
+        Some(path) => path,
+        None => return,
+    };
+    let state = scope.get_slot::<State>().unwrap().clone();
+    let data = state.borrow().files.get(&path).cloned();
+    match data {
+        Some(data) => host::return_string(scope, &mut rv, &data),
+        None => host::throw_error(scope, &format!("ENOENT: no such file: {}", path)),
+    }
+}
+
+fn fs_write_file(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, _rv: rusty_v8::ReturnValue) {
+    let path = match path_arg(scope, &args) {
+        Some(path) => path,
+        None => return,
+    };
+    let data = match host::string_arg(scope, &args, 1, "data") {
+        Some(data) => data,
+        None => return,
+    };
+    let state = scope.get_slot::<State>().unwrap().clone();
+    let written = state.borrow_mut().write(path, data);
+    if let Err(e) = written {
+        host::throw_error(scope, &e);
+    }
+}
+
+fn fs_exists(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    if let Some(path) = path_arg(scope, &args) {
+        let state = scope.get_slot::<State>().unwrap().clone();
+        let exists = state.borrow().files.contains_key(&path);
+        rv.set(rusty_v8::Boolean::new(scope, exists).into());
+    }
+}
+
+fn fs_unlink(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, _rv: rusty_v8::ReturnValue) {
+    let path = match path_arg(scope, &args) {
+        Some(path) => path,
+        None => return,
+    };
+    let state = scope.get_slot::<State>().unwrap().clone();
+    let removed = state.borrow_mut().files.remove(&path);
+    match removed {
+        Some(data) => state.borrow_mut().bytes -= path.len() + data.len(),
+        None => host::throw_error(scope, &format!("ENOENT: no such file: {}", path)),
+    }
+}
+
+fn fs_readdir(scope: &mut rusty_v8::HandleScope, args: rusty_v8::FunctionCallbackArguments, mut rv: rusty_v8::ReturnValue) {
+    let dir = if args.get(0).is_undefined() {
+        String::new()
+    } else {
+        match path_arg(scope, &args) {
+            Some(dir) => dir,
+            None => return,
+        }
+    };
+    let prefix = format!("{}/", dir);
+    let state = scope.get_slot::<State>().unwrap().clone();
+    let mut names: Vec<String> = Vec::new();
+    for path in state.borrow().files.keys() {
+        if let Some(rest) = path.strip_prefix(&prefix) {
+            let name = rest.split('/').next().unwrap_or(rest);
+            if names.last().map(String::as_str) != Some(name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    let names: Vec<_> = names
+        .iter()
+        .map(|name| rusty_v8::String::new(scope, name).unwrap().into())
+        .collect();
+    rv.set(rusty_v8::Array::new_with_elements(scope, &names).into());
+}
+
+pub fn remaining(scope: &mut rusty_v8::HandleScope) -> Option<usize> {
+    let state = scope.get_slot::<State>()?;
+    let vfs = state.borrow();
+    Some(vfs.quota - vfs.bytes)
+}
+
+pub fn install(scope: &mut rusty_v8::HandleScope, vfs: Vfs) {
+    let object = rusty_v8::Object::new(scope);
+    host::set_function(scope, object, "readFile", fs_read_file);
+    host::set_function(scope, object, "writeFile", fs_write_file);
+    host::set_function(scope, object, "exists", fs_exists);
+    host::set_function(scope, object, "unlink", fs_unlink);
+    host::set_function(scope, object, "readdir", fs_readdir);
+    host::set_global(scope, "fs", object.into());
+    scope.set_slot::<State>(Rc::new(RefCell::new(vfs)));
+}
+
+pub fn take(isolate: &mut rusty_v8::Isolate) -> Option<Vfs> {
+    isolate
+        .remove_slot::<State>()
+        .and_then(|state| Rc::try_unwrap(state).ok())
+        .map(RefCell::into_inner)
+}
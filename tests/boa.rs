@@ -0,0 +1,77 @@
+#![cfg(feature = "boa")]
+
+use bot_script_runner::config::{Config, EngineKind};
+use bot_script_runner::engine;
+use serde_json::{json, Value};
+
+fn run(doc: Value, config: &Config) -> Value {
+    let engine = engine::select(EngineKind::Boa).unwrap();
+    let input = serde_json::from_value(doc).unwrap();
+    serde_json::to_value(engine::run(&*engine, &input, config, None)).unwrap()
+}
+
+#[test]
+fn evaluates_scripts() {
+    let doc = run(json!({ "script": "'x'.repeat(3) + (1 + 1)" }), &Config::default());
+    assert_eq!(doc["result"], "xxx2");
+}
+
+#[test]
+fn reports_errors_like_v8() {
+    let doc = run(json!({ "script": "throw new Error('boom')" }), &Config::default());
+    assert_eq!(doc["error"], "Uncaught Error: boom");
+    assert_eq!(doc["error_code"], "E_EXCEPTION");
+    assert_eq!(run(json!({ "script": "1 +" }), &Config::default())["error_code"], "E_SYNTAX");
+}
+
+#[test]
+fn loop_limit_stops_runaway_scripts() {
+    let config = Config { boa_loop_limit: 1000, ..Config::default() };
+    let doc = run(json!({ "script": "while (true) {}" }), &config);
+    assert_eq!(doc["error_kind"], "script");
+}
+
+#[test]
+fn unsupported_limits_are_rejected() {
+    let config = Config::from_args(vec!["--engine=boa".to_string(), "--timeout-ms=100".to_string()].into_iter()).unwrap();
+    assert_eq!(config.engine, EngineKind::Boa);
+    assert_eq!(run(json!({ "script": "1" }), &config)["error"], "Unsupported: --timeout-ms (boa)");
+}
+
+#[test]
+fn unsupported_host_apis_are_rejected() {
+    let config = Config::default();
+    assert_eq!(run(json!({ "script": "console.log(1)" }), &config)["error"], "Unsupported: console (boa)");
+    assert_eq!(run(json!({ "script": "format.escape('*')" }), &config)["error_code"], "E_UNSUPPORTED");
+    assert_eq!(run(json!({ "script": "ctx['requestId']" }), &config)["error"], "Unsupported: ctx (boa)");
+    for (script, api) in [("calc.eval('1 + 1')", "calc"), ("data.parseCSV('a')", "data"), ("fs.exists('/x')", "fs"), ("random.pick([1])", "random"), ("search.distance('a', 'b')", "search"), ("chart.render({ values: [1] })", "chart")] {
+        assert_eq!(run(json!({ "script": script }), &config)["error"], format!("Unsupported: {} (boa)", api));
+    }
+    assert_eq!(run(json!({ "script": "const data = { n: 1 }; let search = [2]; data.n + search[0]" }), &config)["result"], "3");
+    assert_eq!(run(json!({ "script": "const x = { ctx: 1, format: 2 }; x.ctx + x.format" }), &config)["result"], "3");
+    let config = Config::from_args(vec!["--disabled-apis=console".to_string()].into_iter()).unwrap();
+    assert_eq!(run(json!({ "script": "typeof console" }), &config)["error"], "");
+}
+
+#[test]
+fn calls_main_with_args() {
+    let doc = run(json!({ "script": "function main(a, b) { return a.n + b } 'completion'", "args": [{ "n": 1 }, 2] }), &Config::default());
+    assert_eq!(doc["result"], "3");
+    assert_eq!(run(json!({ "script": "const main = 1; 'completion'" }), &Config::default())["result"], "completion");
+}
+
+#[test]
+fn calls_the_named_entry() {
+    let script = "function a() { return 'a' } function b(x) { return 'b' + x } function main() { return 'main' }";
+    assert_eq!(run(json!({ "script": script, "entry": "b", "args": [1] }), &Config::default())["result"], "b1");
+    let doc = run(json!({ "script": script, "entry": "missing" }), &Config::default());
+    assert_eq!(doc["error_code"], "E_UNKNOWN_ENTRY");
+    assert_eq!(doc["error"], "Unknown entry: missing");
+}
+
+#[test]
+fn runs_calls_in_sequence() {
+    let script = "var n = 0; function inc(by) { n += by; return n }";
+    let doc = run(json!({ "script": script, "calls": [{ "entry": "inc", "args": [2] }, { "entry": "inc", "args": [3] }] }), &Config::default());
+    assert_eq!(doc["result"], json!(["2", "5"]));
+}
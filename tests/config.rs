@@ -0,0 +1,41 @@
+use bot_script_runner::config::Config;
+use std::time::Duration;
+
+fn config(args: &[&str]) -> Result<Config, String> {
+    Config::from_args(args.iter().map(|s| s.to_string()))
+}
+
+#[test]
+fn v8_flags_are_checked_against_the_allowlist() {
+    let c = config(&["--v8-flags=--jitless --max_old_space_size=64", "--v8-flags", "--no-opt"]).unwrap();
+    assert_eq!(c.v8_flags, ["--jitless", "--max-old-space-size=64", "--no-opt"]);
+    assert_eq!(config(&["--v8-flags=--allow-natives-syntax"]).err().unwrap(), "V8 flag not allowed: --allow-natives-syntax");
+    assert!(config(&["--v8-flags=--stack-size=big"]).is_err());
+    assert!(config(&["--v8-flags=jitless"]).is_err());
+}
+
+#[test]
+fn hardened_profile_tightens_limits() {
+    let c = config(&["--hardened"]).unwrap();
+    for flag in ["--jitless", "--no-expose-wasm", "--disallow-code-generation-from-strings"].iter() {
+        assert!(c.v8_flags.iter().any(|f| f == flag), "{}", flag);
+    }
+    assert_eq!(c.max_heap_bytes, Some(16 * 1024 * 1024));
+    assert_eq!(c.timeout, Some(Duration::from_secs(2)));
+    assert_eq!(c.max_log_lines, 100);
+    assert_eq!(c.max_log_bytes, 16 * 1024);
+    assert_eq!(c.max_parse_bytes, 1024 * 1024);
+    let c = config(&["--hardened", "--timeout-ms=60000", "--max-heap-mb=8", "--max-log-lines=10"]).unwrap();
+    assert_eq!(c.timeout, Some(Duration::from_secs(2)));
+    assert_eq!(c.max_heap_bytes, Some(8 * 1024 * 1024));
+    assert_eq!(c.max_log_lines, 10);
+    assert_eq!(config(&[]).unwrap().timeout, None);
+}
+
+#[test]
+fn heap_sizes_that_overflow_are_rejected() {
+    let huge = format!("--max-heap-mb={}", usize::MAX / 1024);
+    assert_eq!(config(&[&huge]).err().unwrap(), format!("invalid value for --max-heap-mb: {}", usize::MAX / 1024));
+    assert!(config(&["--soft-heap-mb=18446744073709551615"]).is_err());
+    assert_eq!(config(&["--max-heap-mb=64"]).unwrap().max_heap_bytes, Some(64 * 1024 * 1024));
+}
@@ -0,0 +1,169 @@
+use bot_script_runner::config::Config;
+use bot_script_runner::engine::{self, Capabilities, Cancel, Engine};
+use bot_script_runner::moderation::{CommandModerator, OutputModerator, Verdict};
+use std::time::{Duration, Instant};
+use bot_script_runner::protocol::{Input, ScriptResult};
+
+struct Echo;
+
+impl Engine for Echo {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            calc: false,
+            chart: false,
+            console: false,
+            ctx: false,
+            data: false,
+            datasets: false,
+            format: false,
+            random: false,
+            search: false,
+            extended_results: false,
+            alloc_profile: false,
+            host_trace: false,
+            fs: false,
+            heap_limit: false,
+            timeout: false,
+        }
+    }
+
+    fn exec(&self, input: &Input, _config: &Config, _cancel: Option<&Cancel>) -> ScriptResult {
+        ScriptResult::ok(serde_json::Value::String(input.script.clone()))
+    }
+}
+
+fn input(doc: serde_json::Value) -> Input {
+    serde_json::from_value(doc).unwrap()
+}
+
+#[test]
+fn supported_inputs_reach_the_engine() {
+    let res = engine::run(&Echo, &input(serde_json::json!({ "script": "hi" })), &Config::default(), None);
+    assert_eq!(res.result, "hi");
+    assert!(res.error_kind.is_none());
+}
+
+#[test]
+fn missing_capabilities_are_rejected() {
+    let cases = [
+        serde_json::json!({ "script": "1", "datasets": ["a"] }),
+        serde_json::json!({ "script": "1", "result_format": "extended" }),
+        serde_json::json!({ "script": "1", "profile_alloc": true }),
+    ];
+    for case in cases.iter() {
+        let res = engine::run(&Echo, &input(case.clone()), &Config::default(), None);
+        let doc = serde_json::to_value(&res).unwrap();
+        assert_eq!(doc["error_code"], "E_UNSUPPORTED", "{}", case);
+        assert!(doc["error"].as_str().unwrap().ends_with("(echo)"));
+    }
+}
+
+#[test]
+fn output_filters_rewrite_results_and_count_hits() {
+    let config = Config::from_args(["--redact=tok_[a-z0-9]+", "--max-mentions=1"].iter().map(|s| s.to_string())).unwrap();
+    let script = "key tok_abc123 for <@1> and <@2> and tok_z";
+    let res = engine::run(&Echo, &input(serde_json::json!({ "script": script })), &config, None);
+    assert_eq!(res.result, "key [redacted] for <@1> and <@\u{200b}2> and [redacted]");
+    let doc = serde_json::to_value(&res).unwrap();
+    assert_eq!(doc["stats"]["filter_hits"], serde_json::json!({ "mentions": 1, "redact": 2 }));
+}
+
+struct Outputs;
+
+impl Engine for Outputs {
+    fn name(&self) -> &'static str {
+        "outputs"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities { host_trace: true, fs: true, ..Echo.capabilities() }
+    }
+
+    fn exec(&self, input: &Input, _config: &Config, _cancel: Option<&Cancel>) -> ScriptResult {
+        let mut res = ScriptResult::ok(serde_json::Value::Null);
+        if input.return_files {
+            res.files = Some(std::iter::once(("/out.txt".to_string(), input.script.clone())).collect());
+        }
+        if input.trace_host_calls {
+            res.host_calls = Some(serde_json::json!({ "calls": [{ "call": "console.log", "args": [input.script] }], "dropped": 0 }));
+        }
+        res
+    }
+}
+
+#[test]
+fn output_filters_cover_returned_files() {
+    let config = Config::from_args(["--redact=tok_[a-z0-9]+"].iter().map(|s| s.to_string())).unwrap();
+    let res = engine::run(&Outputs, &input(serde_json::json!({ "script": "key tok_abc123", "return_files": true })), &config, None);
+    assert_eq!(res.files.unwrap()["/out.txt"], "key [redacted]");
+    assert_eq!(res.stats.filter_hits["redact"], 1);
+    let input = input(serde_json::json!({ "script": "key tok_abc123", "trace_host_calls": true }));
+    let res = engine::run(&Outputs, &input, &config, None);
+    assert_eq!(res.stats.filter_hits["redact"], 1);
+    assert_eq!(res.host_calls.unwrap()["calls"][0]["args"][0], "key [redacted]");
+}
+
+struct Keyword;
+
+impl OutputModerator for Keyword {
+    fn name(&self) -> &str {
+        "keyword"
+    }
+
+    fn moderate(&self, res: &ScriptResult) -> Result<Verdict, String> {
+        match res.result.as_str() {
+            Some("spam") => Ok(Verdict::Veto { reason: "spam".to_string() }),
+            Some("flag") => Ok(Verdict::Annotate { labels: serde_json::json!(["flagged"]) }),
+            Some("down") => Err("unreachable".to_string()),
+            _ => Ok(Verdict::Allow),
+        }
+    }
+}
+
+#[test]
+fn moderator_can_veto_or_annotate_output() {
+    let mut config = Config { moderator: Some(Box::new(Keyword)), ..Config::default() };
+    let run = |script: &str, config: &Config| {
+        let res = engine::run(&Echo, &input(serde_json::json!({ "script": script })), config, None);
+        serde_json::to_value(&res).unwrap()
+    };
+    assert_eq!(run("ok", &config)["result"], "ok");
+    let doc = run("spam", &config);
+    assert_eq!(doc["error_code"], "E_MODERATED");
+    assert_eq!(doc["result"], "");
+    assert_eq!(doc["error_detail"]["reason"], "spam");
+    assert_eq!(run("flag", &config)["moderation"], serde_json::json!(["flagged"]));
+    assert_eq!(run("down", &config)["error_code"], "E_MODERATED");
+    config.moderation_fail_open = true;
+    assert_eq!(run("down", &config)["result"], "down");
+}
+
+#[test]
+fn command_moderator_is_bounded_by_its_timeout() {
+    let res = ScriptResult::ok(serde_json::Value::String("x".repeat(1 << 20)));
+    let started = Instant::now();
+    let hung = CommandModerator::parse("sleep 10", Duration::from_millis(100)).unwrap();
+    assert_eq!(hung.moderate(&res), Err("timed out after 100 ms".to_string()));
+    let echo = CommandModerator::parse("cat", Duration::from_secs(5)).unwrap();
+    assert!(echo.moderate(&res).is_err());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn requires_header_is_checked_for_every_engine() {
+    let script = "// bot command\n// @requires manage_messages\n'x'";
+    let res = engine::run(&Echo, &input(serde_json::json!({ "script": script })), &Config::default(), None);
+    assert_eq!(res.error, "Permission denied: manage_messages");
+    let granted = serde_json::json!({ "script": script, "permissions": ["manage_messages"] });
+    assert!(engine::run(&Echo, &input(granted), &Config::default(), None).error_code.is_none());
+}
+
+#[test]
+fn errors_are_localized_for_every_front_end() {
+    let res = engine::run(&Echo, &input(serde_json::json!({ "script": "// @requires ban\n1", "lang": "ja" })), &Config::default(), None);
+    assert_eq!(res.error, "権限がありません: ban");
+}
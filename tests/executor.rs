@@ -0,0 +1,57 @@
+#![cfg(feature = "async")]
+
+use bot_script_runner::config::Config;
+use bot_script_runner::executor::Executor;
+use bot_script_runner::protocol::Input;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+fn input(script: &str) -> Input {
+    serde_json::from_value(serde_json::json!({ "script": script })).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn runs_scripts_off_the_runtime() {
+    let executor = Executor::new(2, Config::default()).unwrap();
+    let (a, b) = tokio::join!(
+        executor.run(input("1 + 1"), CancellationToken::new()),
+        executor.run(input("'x'.repeat(3)"), CancellationToken::new()),
+    );
+    assert_eq!(a.result, "2");
+    assert_eq!(b.result, "xxx");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cancellation_terminates_the_script() {
+    let executor = Executor::new(1, Config::default()).unwrap();
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancel.cancel();
+    });
+    let res = executor.run(input("while (true) {}"), token).await;
+    assert_eq!(serde_json::to_value(&res).unwrap()["error_code"], "E_CANCELLED");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn requests_go_through_the_engine_pipeline() {
+    let config = Config::from_args(["--redact=tok_[a-z0-9]+"].iter().map(|s| s.to_string())).unwrap();
+    let executor = Executor::new(1, config).unwrap();
+    let res = executor.run(input("// @requires ban\n1"), CancellationToken::new()).await;
+    assert_eq!(serde_json::to_value(&res).unwrap()["error_code"], "E_PERMISSION_DENIED");
+    let res = executor.run(input("'key tok_abc123'"), CancellationToken::new()).await;
+    assert_eq!(res.result, "key [redacted]");
+    let mut ja = input("// @requires ban\n1");
+    ja.lang = bot_script_runner::messages::Lang::Ja;
+    let res = executor.run(ja, CancellationToken::new()).await;
+    assert_eq!(res.error, "権限がありません: ban");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn queue_time_is_measured_per_request() {
+    let executor = Executor::new(1, Config::default()).unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let res = executor.run(input("1"), CancellationToken::new()).await;
+    assert!(res.stats.queued_ms < 200.0, "{}", res.stats.queued_ms);
+}
@@ -0,0 +1,26 @@
+#![cfg(feature = "v8")]
+
+use bot_script_runner::host;
+
+fn explode(_scope: &mut rusty_v8::HandleScope, _args: rusty_v8::FunctionCallbackArguments, _rv: rusty_v8::ReturnValue) {
+    panic!("host callback bug");
+}
+
+#[test]
+fn host_callback_panics_terminate_the_script() {
+    bot_script_runner::initialize(&[]).unwrap();
+    let isolate = &mut rusty_v8::Isolate::new(Default::default());
+    let completed = {
+        let scope = &mut rusty_v8::HandleScope::new(isolate);
+        let context = rusty_v8::Context::new(scope);
+        let scope = &mut rusty_v8::ContextScope::new(scope, context);
+        let global = context.global(scope);
+        host::set_function(scope, global, "explode", explode);
+        let code = rusty_v8::String::new(scope, "try { explode() } catch (e) {} 'survived'").unwrap();
+        let script = rusty_v8::Script::compile(scope, code, None).unwrap();
+        script.run(scope).is_some()
+    };
+    assert!(!completed);
+    assert!(host::panicked(isolate));
+    assert!(!host::panicked(isolate));
+}
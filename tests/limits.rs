@@ -0,0 +1,54 @@
+#![cfg(feature = "v8")]
+
+use bot_script_runner::config::Config;
+use bot_script_runner::protocol::Input;
+use proptest::prelude::*;
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_millis(100);
+const DEADLINE_SLACK: Duration = Duration::from_millis(500);
+const MAX_HEAP_BYTES: usize = 16 * 1024 * 1024;
+const HEAP_SLACK_BYTES: usize = 8 * 1024 * 1024;
+
+fn config() -> Config {
+    Config {
+        max_heap_bytes: Some(MAX_HEAP_BYTES),
+        timeout: Some(TIMEOUT),
+        ..Config::default()
+    }
+}
+
+fn allocation() -> impl Strategy<Value = &'static str> {
+    prop_oneof![
+        Just(""),
+        Just("keep.push(new Array(1000).fill(i));"),
+        Just("keep.push('x'.repeat(1000) + i);"),
+        Just("keep.push({ i, s: String(i) });"),
+        Just("keep = [];"),
+    ]
+}
+
+fn script() -> impl Strategy<Value = String> {
+    (prop_oneof![Just(None), (0u32..200_000).prop_map(Some)], allocation(), any::<bool>()).prop_map(|(bound, alloc, throws)| {
+        let cond = bound.map_or("true".to_string(), |n| format!("i < {}", n));
+        let tail = if throws { "throw new Error('done')" } else { "keep.length" };
+        format!("let keep = []; for (let i = 0; {}; i++) {{ {} }} {}", cond, alloc, tail)
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn limits_always_hold(script in script()) {
+        bot_script_runner::initialize(&[]).unwrap();
+        let input: Input = serde_json::from_value(serde_json::json!({ "script": script })).unwrap();
+        let start = Instant::now();
+        let res = bot_script_runner::exec_v8(&input, &config());
+        prop_assert!(start.elapsed() < TIMEOUT + DEADLINE_SLACK, "took {:?}", start.elapsed());
+        prop_assert!(res.stats.heap_bytes <= 2 * MAX_HEAP_BYTES + HEAP_SLACK_BYTES, "heap {}", res.stats.heap_bytes);
+        let doc = serde_json::to_value(&res).unwrap();
+        prop_assert!(doc.get("result").is_some());
+        prop_assert!(doc["error"].is_string());
+    }
+}
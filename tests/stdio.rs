@@ -0,0 +1,621 @@
+#![cfg(feature = "v8")]
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bot_script_runner"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+fn result_document(output: &Output) -> serde_json::Value {
+    let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+    let doc: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert!(doc.get("result").is_some());
+    assert!(doc["error"].is_string());
+    doc
+}
+
+#[test]
+fn success_writes_only_the_result() {
+    let output = run(&[], r#"{"script":"1 + 1"}"#);
+    let doc = result_document(&output);
+    assert_eq!(doc["result"], "2");
+    assert_eq!(doc["error"], "");
+    assert!(doc.get("error_kind").is_none());
+    assert!(output.stderr.is_empty());
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn script_error_is_a_single_document() {
+    let output = run(&[], r#"{"script":"throw new Error('x')"}"#);
+    assert_eq!(output.status.code(), Some(1));
+    let doc = result_document(&output);
+    assert_eq!(doc["result"], "");
+    assert_eq!(doc["error"], "Uncaught Error: x");
+    assert_eq!(doc["error_kind"], "script");
+}
+
+#[test]
+fn malformed_input_is_a_single_document() {
+    let output = run(&[], "{not json");
+    assert_eq!(output.status.code(), Some(2));
+    let doc = result_document(&output);
+    assert_eq!(doc["error"], "Invalid input");
+    assert_eq!(doc["error_kind"], "protocol");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("[warn]"));
+}
+
+#[test]
+fn empty_input_is_a_single_document() {
+    let doc = result_document(&run(&[], ""));
+    assert_eq!(doc["error"], "Invalid input");
+}
+
+#[test]
+fn bad_arguments_are_a_single_document() {
+    let output = run(&["--no-such-flag"], r#"{"script":"1"}"#);
+    assert_eq!(output.status.code(), Some(2));
+    let doc = result_document(&output);
+    assert_eq!(doc["error"], "Error");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown argument"));
+}
+
+#[test]
+fn debug_logs_go_to_stderr() {
+    let output = run(&["--log-level=debug", "--v8-flags=--no-opt"], r#"{"script":"'ok'"}"#);
+    assert_eq!(result_document(&output)["result"], "ok");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("[debug] v8 flags: --no-opt"));
+}
+
+#[test]
+fn timeout_has_its_own_exit_code() {
+    let output = run(&["--timeout-ms=50"], r#"{"script":"while (true) {}"}"#);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(result_document(&output)["error_kind"], "timeout");
+}
+
+#[test]
+fn memory_limit_has_its_own_exit_code() {
+    let output = run(&["--max-heap-mb=16"], r#"{"script":"const a = []; while (true) { a.push(new Array(1000).fill(1)); }"}"#);
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(result_document(&output)["error_kind"], "oom");
+}
+
+#[test]
+fn datasets_are_loaded_on_access() {
+    let path = std::env::temp_dir().join(format!("bsr-dataset-{}.json", std::process::id()));
+    std::fs::write(&path, r#"{"words":["a","b","c"],"meta":{"lang":"en","size":3}}"#).unwrap();
+    let arg = format!("--dataset=vocab={}", path.display());
+    let unused = format!("--dataset=unused={}", path.display());
+    let script = "const v = datasets.vocab; v.words.map((w) => w.toUpperCase()).join('') + v.words.length + v.meta.lang + (v.meta === v.meta)";
+    let output = run(&[&arg, &unused], &serde_json::json!({ "script": script, "datasets": ["vocab", "unused"] }).to_string());
+    std::fs::remove_file(&path).unwrap();
+    let doc = result_document(&output);
+    assert_eq!(doc["result"], "ABC3entrue");
+    assert_eq!(doc["stats"]["datasets_accessed"], serde_json::json!(["vocab"]));
+    assert_eq!(doc["stats"]["dataset_paths"], serde_json::json!(["vocab.meta", "vocab.meta.lang", "vocab.words", "vocab.words[]"]));
+}
+
+#[test]
+fn unknown_datasets_are_rejected() {
+    let output = run(&[], r#"{"script":"1","datasets":["missing"]}"#);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(result_document(&output)["error"], "Unknown dataset: missing");
+}
+
+#[test]
+fn console_output_is_captured() {
+    let doc = result_document(&run(&[], r#"{"script":"console.log('a', 1, {b: 2}); console.error(new Error('e')); 0"}"#));
+    assert_eq!(doc["logs"][0]["level"], "log");
+    assert_eq!(doc["logs"][0]["text"], "a 1 {\"b\":2}");
+    assert!(doc["logs"][0]["t_ms"].is_u64());
+    assert_eq!(doc["logs"][1]["level"], "error");
+    assert_eq!(doc["logs"][1]["text"], "Error: e");
+    assert!(doc.get("logs_truncated").is_none());
+}
+
+#[test]
+fn console_output_is_capped() {
+    let output = run(&["--max-log-lines=3"], r#"{"script":"for (let i = 0; i < 1e6; i++) console.log(i); 'done'"}"#);
+    let doc = result_document(&output);
+    assert_eq!(doc["result"], "done");
+    let texts: Vec<_> = doc["logs"].as_array().unwrap().iter().map(|l| l["text"].clone()).collect();
+    assert_eq!(texts, vec!["0", "1", "2"]);
+    assert_eq!(doc["logs_truncated"], true);
+
+    let doc = result_document(&run(&["--max-log-bytes=5"], r#"{"script":"console.log('abc'); console.log('defg'); console.log('h'); 0"}"#));
+    assert_eq!(doc["logs"].as_array().unwrap().len(), 1);
+    assert_eq!(doc["logs"][0]["text"], "abc");
+    assert_eq!(doc["logs_truncated"], true);
+}
+
+#[test]
+fn format_helpers_build_chat_output() {
+    let script = r#"[
+        format.escape('*hi* _there_'),
+        format.codeblock('a```b', 'js'),
+        format.table([{name: 'alice', score: 10}, {name: 'ボブ', score: 7}])
+    ].join('\n~~\n')"#;
+    let input = serde_json::json!({ "script": script }).to_string();
+    let doc = result_document(&run(&[], &input));
+    assert_eq!(
+        doc["result"],
+        "\\*hi\\* \\_there\\_\n~~\n```js\na`\u{200b}``b\n```\n~~\nname  | score\n------+------\nalice | 10\nボブ  | 7"
+    );
+}
+
+#[test]
+fn format_helpers_reject_bad_arguments() {
+    let doc = result_document(&run(&[], r#"{"script":"format.table(5)"}"#));
+    assert_eq!(doc["error"], "Uncaught TypeError: rows must be an array");
+    let script = r#"[-1, 1.5, NaN, Infinity, '3'].map((max) => { try { return format.truncate('abcdef', max) } catch (e) { return e.message } }).join('|')"#;
+    let doc = result_document(&run(&[], &serde_json::json!({ "script": script }).to_string()));
+    assert_eq!(doc["result"], ["max must be a non-negative integer"; 5].join("|"));
+}
+
+#[test]
+fn truncation_keeps_grapheme_clusters_whole() {
+    let script = r#"[
+        format.truncate('👨‍👩‍👧‍👦👍🏽abc', 3),
+        format.truncate('日本語テキスト', 9, {unit: 'bytes', ellipsis: ''}),
+        format.truncate('short', 10),
+        format.normalize('e\u0301') === '\u00e9'
+    ].join('|')"#;
+    let input = serde_json::json!({ "script": script }).to_string();
+    let doc = result_document(&run(&[], &input));
+    assert_eq!(doc["result"], "👨‍👩‍👧‍👦👍🏽…|日本語|short|true");
+}
+
+#[test]
+fn extended_results_are_tagged_json() {
+    let script = r#"
+        const o = {n: 1, big: 10n ** 20n, when: new Date(0), m: new Map([['k', new Set([1, 2])]]), u: undefined, nan: NaN};
+        o.self = o;
+        o
+    "#;
+    let input = serde_json::json!({ "script": script, "result_format": "extended" }).to_string();
+    let doc = result_document(&run(&[], &input));
+    assert_eq!(
+        doc["result"],
+        serde_json::json!({
+            "n": 1,
+            "big": {"$type": "bigint", "value": "100000000000000000000"},
+            "when": {"$type": "date", "value": "1970-01-01T00:00:00.000Z"},
+            "m": {"$type": "map", "entries": [["k", {"$type": "set", "values": [1, 2]}]]},
+            "u": {"$type": "undefined"},
+            "nan": {"$type": "number", "value": "NaN"},
+            "self": {"$type": "ref", "path": "$"}
+        })
+    );
+}
+
+#[test]
+fn oversized_results_are_rejected() {
+    let output = run(&["--max-result-bytes=100"], r#"{"script":"'x'.repeat(1000)"}"#);
+    assert_eq!(result_document(&output)["error"], "Result too large");
+    let output = run(&["--max-result-bytes=100"], r#"{"script":"Array(1000).fill('x')","result_format":"extended"}"#);
+    assert_eq!(result_document(&output)["error"], "Result too large");
+    let output = run(&[], r#"{"script":"new Array(2**32-1)","result_format":"extended"}"#);
+    assert_eq!(result_document(&output)["error"], "Result too large");
+}
+
+#[test]
+fn thrown_errors_keep_their_structure() {
+    let script = r#"
+        const root = new RangeError('low', {cause: 'disk'});
+        const err = new Error('top', {cause: root});
+        err.code = 'E42';
+        throw err;
+    "#;
+    let input = serde_json::json!({ "script": script }).to_string();
+    let doc = result_document(&run(&[], &input));
+    assert_eq!(doc["error"], "Uncaught Error: top");
+    let detail = &doc["error_detail"];
+    assert_eq!(detail["name"], "Error");
+    assert_eq!(detail["message"], "top");
+    assert!(detail["stack"].as_str().unwrap().starts_with("Error: top"));
+    assert_eq!(detail["props"], serde_json::json!({"code": "E42"}));
+    assert_eq!(detail["cause"]["name"], "RangeError");
+    assert_eq!(detail["cause"]["cause"], serde_json::json!({"value": "disk"}));
+}
+
+#[test]
+fn thrown_non_errors_are_encoded() {
+    let doc = result_document(&run(&[], r#"{"script":"throw {reason: 'nope', n: 1n}"}"#));
+    assert_eq!(doc["error_detail"], serde_json::json!({"value": {"reason": "nope", "n": {"$type": "bigint", "value": "1"}}}));
+}
+
+#[test]
+fn oversized_scripts_are_rejected() {
+    let output = run(&["--max-script-bytes=10"], r#"{"script":"'this is more than ten bytes'"}"#);
+    assert_eq!(output.status.code(), Some(2));
+    let doc = result_document(&output);
+    assert_eq!(doc["error"], "Script too large");
+    assert_eq!(doc["error_kind"], "protocol");
+
+    let input = serde_json::json!({ "script": format!("'{}'", "x".repeat(100_000)) }).to_string();
+    let output = run(&["--max-script-bytes=10"], &input);
+    assert_eq!(result_document(&output)["error"], "Script too large");
+}
+
+fn gzip_envelope(input: &str) -> String {
+    use base64::Engine;
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(input.as_bytes()).unwrap();
+    let payload = base64::engine::general_purpose::STANDARD.encode(encoder.finish().unwrap());
+    serde_json::json!({ "compression": "gzip", "payload": payload }).to_string()
+}
+
+#[test]
+fn compressed_input_is_accepted() {
+    let doc = result_document(&run(&[], &gzip_envelope(r#"{"script":"'zipped'"}"#)));
+    assert_eq!(doc["result"], "zipped");
+}
+
+#[test]
+fn compression_bombs_are_rejected() {
+    let input = serde_json::json!({ "script": format!("'{}'", " ".repeat(5_000_000)) }).to_string();
+    let output = run(&[], &gzip_envelope(&input));
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(result_document(&output)["error"], "Script too large");
+}
+
+#[test]
+fn runner_errors_are_localized() {
+    let output = run(&["--timeout-ms=50"], r#"{"script":"while (true) {}","lang":"ja"}"#);
+    assert_eq!(result_document(&output)["error"], "タイムアウトしました");
+    let output = run(&[], r#"{"script":"throw new Error('Timeout')","lang":"ja"}"#);
+    assert_eq!(result_document(&output)["error"], "Uncaught Error: Timeout");
+}
+
+#[test]
+fn errors_carry_stable_codes() {
+    let cases = [
+        (&[][..], r#"{"script":"1 +"}"#, "E_SYNTAX"),
+        (&[][..], r#"{"script":"throw 1"}"#, "E_EXCEPTION"),
+        (&["--timeout-ms=50"][..], r#"{"script":"while (true) {}"}"#, "E_TIMEOUT"),
+        (&[][..], "not json", "E_INVALID_INPUT"),
+        (&["--bogus"][..], r#"{"script":"1"}"#, "E_BAD_ARGS"),
+    ];
+    for (args, input, code) in cases.iter() {
+        assert_eq!(result_document(&run(args, input))["error_code"], *code, "{}", input);
+    }
+    assert!(result_document(&run(&[], r#"{"script":"1"}"#)).get("error_code").is_none());
+}
+
+#[test]
+fn soft_heap_limit_is_reported_without_terminating() {
+    let script = r#"{"script":"const a = []; for (let i = 0; i < 12000; i++) a.push(new Array(1000).fill(i)); a.length"}"#;
+    let output = run(&["--soft-heap-mb=16", "--max-heap-mb=512"], script);
+    assert_eq!(output.status.code(), Some(0));
+    let doc = result_document(&output);
+    assert_eq!(doc["result"], "12000");
+    assert_eq!(doc["stats"]["soft_heap_limit_hit"], true);
+}
+
+#[test]
+fn soft_heap_limit_requires_a_higher_hard_limit() {
+    let output = run(&["--soft-heap-mb=32", "--max-heap-mb=16"], r#"{"script":"1"}"#);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn allocation_profile_lists_top_sites() {
+    let script = r#"{"script":"function grow() { const a = []; for (let i = 0; i < 100000; i++) a.push({ i }); return a; }\ngrow().length","profile_alloc":true}"#;
+    let doc = result_document(&run(&[], script));
+    let sites = doc["alloc_profile"].as_array().unwrap();
+    assert!(!sites.is_empty());
+    assert!(sites.iter().any(|s| s["function"] == "grow" && s["bytes"].as_u64().unwrap() > 0));
+    assert!(result_document(&run(&[], r#"{"script":"1"}"#)).get("alloc_profile").is_none());
+    assert!(doc.get("alloc_profile_truncated").is_none());
+    let script = r#"{"script":"function deep(n) { if (n === 0) { const a = []; for (let i = 0; i < 100000; i++) a.push({ i }); return a.length; } return deep(n - 1); }\ndeep(300)","profile_alloc":true}"#;
+    let doc = result_document(&run(&[], script));
+    assert_eq!(doc["result"], "100000");
+    assert!(doc["alloc_profile"].is_array());
+    assert_eq!(doc["alloc_profile_truncated"], true);
+}
+
+#[test]
+fn bench_reports_latency_per_script() {
+    let output = run(&["bench", "--iterations=2", "--timeout-ms=5000"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let scripts = report["scripts"].as_array().unwrap();
+    assert!(!scripts.is_empty());
+    for script in scripts {
+        assert_eq!(script["runs"], 2);
+        assert_eq!(script["errors"], 0, "{}", script["name"]);
+        assert!(script["p95_ms"].as_f64().unwrap() >= script["p50_ms"].as_f64().unwrap());
+    }
+}
+
+fn unix_ms(offset_ms: i64) -> i64 {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap();
+    now.as_millis() as i64 + offset_ms
+}
+
+#[test]
+fn expired_deadlines_are_rejected() {
+    let input = format!(r#"{{"script":"1","deadline_unix_ms":{}}}"#, unix_ms(-1000));
+    let output = run(&[], &input);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(result_document(&output)["error_code"], "E_DEADLINE");
+}
+
+#[test]
+fn deadline_caps_the_timeout() {
+    let input = format!(r#"{{"script":"while (true) {{}}","deadline_unix_ms":{}}}"#, unix_ms(200));
+    let start = std::time::Instant::now();
+    let output = run(&["--timeout-ms=60000"], &input);
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    assert_eq!(result_document(&output)["error_code"], "E_TIMEOUT");
+}
+
+#[test]
+fn stats_break_down_latency() {
+    let doc = result_document(&run(&[], r#"{"script":"let s = 0; for (let i = 0; i < 1e5; i++) s += i; s"}"#));
+    for key in ["queued_ms", "compile_ms", "exec_ms", "serialize_ms"].iter() {
+        assert!(doc["stats"][key].as_f64().unwrap() >= 0.0, "{}", key);
+    }
+    assert!(doc["stats"]["exec_ms"].as_f64().unwrap() > 0.0);
+}
+
+#[test]
+fn invalid_fields_are_reported_with_a_path() {
+    let output = run(&[], r#"{"script":"1","datasets":["a",2]}"#);
+    let doc = result_document(&output);
+    assert_eq!(doc["error_code"], "E_INVALID_INPUT");
+    assert_eq!(doc["error_detail"]["path"], "datasets[1]");
+    let doc = result_document(&run(&[], r#"{"script":"1","scirpt":"2"}"#));
+    assert!(doc["error_detail"]["message"].as_str().unwrap().contains("unknown field `scirpt`"));
+}
+
+#[test]
+fn schema_covers_every_input_field() {
+    let schema: serde_json::Value = serde_json::from_str(bot_script_runner::protocol::SCHEMA).unwrap();
+    let props = schema["$defs"]["input"]["properties"].as_object().unwrap();
+    let sample = serde_json::json!({
+        "script": "function main() { return 1 }",
+        "datasets": [],
+        "result_format": "extended",
+        "lang": "ja",
+        "profile_alloc": false,
+        "deadline_unix_ms": u64::MAX,
+        "request_id": "req-1",
+        "args": [],
+        "entry": "main",
+        "calls": [],
+        "permissions": ["admin"],
+        "trace_host_calls": false,
+        "return_files": false,
+        "random_seed": 42,
+    });
+    let keys: Vec<_> = sample.as_object().unwrap().keys().collect();
+    assert_eq!(props.keys().collect::<Vec<_>>(), keys);
+    let output = run(&[], &sample.to_string());
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn request_id_reaches_the_script_and_the_logs() {
+    let input = r#"{"script":"ctx.foo = 1; console.log(ctx.requestId); ctx.requestId + ':' + Object.isFrozen(ctx)","request_id":"abc123"}"#;
+    let output = run(&["--log-level=debug"], input);
+    let doc = result_document(&output);
+    assert_eq!(doc["result"], "abc123:true");
+    assert_eq!(doc["request_id"], "abc123");
+    assert_eq!(doc["logs"][0]["text"], "abc123");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("[abc123]"));
+    assert_eq!(result_document(&run(&[], r#"{"script":"ctx.requestId"}"#))["result"], "null");
+}
+
+#[test]
+fn quota_reports_remaining_limits() {
+    let script = r#""fs.writeFile('/a', 'x'.repeat(24)); const q = ctx.quota; [q.timeMs > 0 && q.timeMs <= 5000, q.heapBytes > 0, q.fsBytes, Object.isFrozen(q)]""#;
+    let input = format!(r#"{{"script":{}}}"#, script);
+    let doc = result_document(&run(&["--timeout-ms=5000", "--max-heap-mb=64", "--fs-quota-kb=1"], &input));
+    assert_eq!(doc["result"], serde_json::json!([true, true, 998, true]));
+    let doc = result_document(&run(&["--disabled-apis=fs"], r#"{"script":"JSON.stringify(ctx.quota)"}"#));
+    assert_eq!(doc["result"], r#"{"timeMs":null,"heapBytes":null,"fsBytes":null}"#);
+}
+
+#[test]
+fn disabled_host_api_throws_capability_error() {
+    let script = r#"{"script":"try { format.escape("x") } catch (e) { `${e instanceof CapabilityError} ${e.api} ${e.message}` }"}"#;
+    let doc = result_document(&run(&["--disabled-apis=format"], script));
+    assert_eq!(doc["result"], "true format format is disabled");
+    let apis = doc["stats"]["apis"].as_array().unwrap();
+    assert!(apis.contains(&serde_json::json!("console")) && !apis.contains(&serde_json::json!("format")));
+    assert_eq!(run(&["--disabled-apis=fetch"], r#"{"script":"1"}"#).status.code(), Some(2));
+}
+
+#[test]
+fn wrapper_keeps_user_line_numbers() {
+    let path = std::env::temp_dir().join(format!("bsr-wrapper-{}.js", std::process::id()));
+    std::fs::write(&path, "\"use strict\";\n(() => {\n%SCRIPT%\n})()").unwrap();
+    let arg = format!("--wrapper={}", path.display());
+    let ok = result_document(&run(&[&arg], r#"{"script":"return 6 * 7"}"#));
+    let err = result_document(&run(&[&arg], r#"{"script":"1;\nthrow new Error('boom')"}"#));
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(ok["result"], "42");
+    assert!(err["error_detail"]["stack"].as_str().unwrap().contains(":2:"));
+}
+
+#[test]
+fn strict_mode_rejects_sloppy_code() {
+    let script = r#"{"script":"with ({ a: 1 }) a"}"#;
+    assert_eq!(result_document(&run(&[], script))["result"], "1");
+    let doc = result_document(&run(&["--strict"], script));
+    assert_eq!(doc["error_code"], "E_SYNTAX");
+    assert_eq!(result_document(&run(&["--strict"], r#"{"script":"(function () { return this })()"}"#))["result"], "undefined");
+}
+
+#[test]
+fn main_or_handler_return_value_wins_over_completion() {
+    let doc = result_document(&run(&[], r#"{"script":"function main(a, b) { return a.n + b }\n'completion'","args":[{"n":1},2]}"#));
+    assert_eq!(doc["result"], "3");
+    let doc = result_document(&run(&[], r#"{"script":"function handler() { return 'h' }\n'completion'"}"#));
+    assert_eq!(doc["result"], "h");
+    assert_eq!(result_document(&run(&[], r#"{"script":"'completion'"}"#))["result"], "completion");
+}
+
+#[test]
+fn entry_selects_a_handler() {
+    let script = r#""function a() { return 'a' }\nfunction b(x) { return 'b' + x }\nfunction main() { return 'main' }""#;
+    let doc = result_document(&run(&[], &format!(r#"{{"script":{},"entry":"b","args":[1]}}"#, script)));
+    assert_eq!(doc["result"], "b1");
+    let output = run(&[], &format!(r#"{{"script":{},"entry":"missing"}}"#, script));
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(result_document(&output)["error_code"], "E_UNKNOWN_ENTRY");
+}
+
+#[test]
+fn calls_share_one_evaluation() {
+    let input = r#"{"script":"let n = 0;\nfunction inc(by) { n += by; return n }\nfunction get() { return { n } }","calls":[{"entry":"inc","args":[2]},{"entry":"inc","args":[3]},{"entry":"get"}],"result_format":"extended"}"#;
+    let doc = result_document(&run(&[], input));
+    assert_eq!(doc["result"], serde_json::json!([2, 5, {"n": 5}]));
+    let output = run(&[], r#"{"script":"1","entry":"a","calls":[{"entry":"b"}]}"#);
+    assert_eq!(result_document(&output)["error_detail"]["path"], "calls");
+}
+
+#[test]
+fn required_permissions_are_enforced_before_running() {
+    let script = r#""// @requires kick, ban\nctx.permissions.join(' ')""#;
+    let doc = result_document(&run(&[], &format!(r#"{{"script":{},"permissions":["kick","ban","mute"]}}"#, script)));
+    assert_eq!(doc["result"], "kick ban mute");
+    let doc = result_document(&run(&[], &format!(r#"{{"script":{},"permissions":["kick"]}}"#, script)));
+    assert_eq!(doc["error_code"], "E_PERMISSION_DENIED");
+    assert_eq!(doc["error"], "Permission denied: ban");
+}
+
+#[test]
+fn host_calls_are_traced_in_order() {
+    let input = r#"{"script":"console.log('hi'); format.escape('*x*')","trace_host_calls":true}"#;
+    let doc = result_document(&run(&[], input));
+    let calls = &doc["host_calls"]["calls"];
+    assert_eq!(calls[0]["call"], "console.log");
+    assert_eq!(calls[0]["args"], serde_json::json!(["hi"]));
+    assert_eq!(calls[1]["call"], "format.escape");
+    assert_eq!(calls[1]["result"], doc["result"]);
+    assert!(result_document(&run(&[], r#"{"script":"1"}"#)).get("host_calls").is_none());
+    let input = r#"{"script":"const o = { n: 1 }; console.log(o); o.n = 2; 0","trace_host_calls":true}"#;
+    let doc = result_document(&run(&[], input));
+    assert_eq!(doc["host_calls"]["calls"][0]["args"], serde_json::json!([{ "n": 1 }]));
+}
+
+#[test]
+fn in_memory_fs_is_scoped_and_quota_limited() {
+    let script = r#""fs.writeFile('/out/a.txt', 'A'); fs.writeFile('out/./b.txt', 'B'); fs.readdir('/out').join() + fs.readFile('/out/a.txt')""#;
+    let doc = result_document(&run(&[], &format!(r#"{{"script":{},"return_files":true}}"#, script)));
+    assert_eq!(doc["result"], "a.txt,b.txtA");
+    assert_eq!(doc["files"], serde_json::json!({"/out/a.txt": "A", "/out/b.txt": "B"}));
+    let doc = result_document(&run(&["--fs-quota-kb=1"], r#"{"script":"fs.writeFile('/big', 'x'.repeat(2048))"}"#));
+    assert!(doc["error"].as_str().unwrap().contains("ENOSPC"));
+    let doc = result_document(&run(&[], r#"{"script":"try { fs.readFile('/missing') } catch (e) { e.message }"}"#));
+    assert_eq!(doc["result"], "ENOENT: no such file: /missing");
+}
+
+#[test]
+fn random_draws_are_seeded_and_recorded() {
+    let script = r#""[random.pick(['a','b','c']), random.shuffle([1,2,3,4]).join(''), random.dice('3d6+2').total]""#;
+    let input = format!(r#"{{"script":{},"random_seed":7}}"#, script);
+    let doc = result_document(&run(&[], &input));
+    assert_eq!(doc, result_document(&run(&[], &input)));
+    let random = &doc["stats"]["random"];
+    assert_eq!(random["seed"], 7);
+    assert_eq!(random["draws"][0]["call"], "pick");
+    assert!(random["draws"][0].get("result").is_none());
+    let picked = ["a", "b", "c"][random["draws"][0]["index"].as_u64().unwrap() as usize];
+    assert_eq!(doc["result"][0], picked);
+    assert_eq!(random["draws"][1]["order"].as_array().unwrap().len(), 4);
+    let dice = &random["draws"][2]["result"];
+    assert_eq!(dice["rolls"].as_array().unwrap().len(), 3);
+    assert_eq!(dice["total"], doc["result"][2]);
+    let doc = result_document(&run(&[], r#"{"script":"try { random.dice('2d0') } catch (e) { e.message }"}"#));
+    assert_eq!(doc["result"], "invalid dice notation: 2d0");
+    assert!(result_document(&run(&[], r#"{"script":"1"}"#))["stats"].get("random").is_none());
+    let doc = result_document(&run(&[], r#"{"script":"try { random.shuffle(new Array(2**32-1)) } catch (e) { e.name + ': ' + e.message }"}"#));
+    assert_eq!(doc["result"], "RangeError: items must have at most 1000000 elements");
+    let doc = result_document(&run(&[], r#"{"script":"random.shuffle(Array.from({ length: 1000 }, (_, i) => i)).length"}"#));
+    let draw = &doc["stats"]["random"]["draws"][0];
+    assert_eq!(draw["length"], 1000);
+    assert_eq!(draw["order"].as_array().unwrap().len(), 100);
+    assert_eq!(draw["order_truncated"], true);
+    let doc = result_document(&run(&["--redact=1d6"], r#"{"script":"random.dice('1d6').rolls.length"}"#));
+    assert_eq!(doc["stats"]["random"]["draws"][0]["notation"], "[redacted]");
+    assert_eq!(doc["stats"]["filter_hits"]["redact"], 1);
+}
+
+#[test]
+fn data_parsers_run_on_the_host() {
+    let script = r#""const rows = data.parseCSV('name,n\\nx,\\\"1,2\\\"\\ny,3', { header: true }); const doc = data.parseYAML('a: [1, b]'); JSON.stringify([rows, doc])""#;
+    let doc = result_document(&run(&[], &format!(r#"{{"script":{}}}"#, script)));
+    assert_eq!(doc["result"], r#"[[{"name":"x","n":"1,2"},{"name":"y","n":"3"}],{"a":[1,"b"]}]"#);
+    let doc = result_document(&run(&["--max-parse-kb=1"], r#"{"script":"data.parseCSV('x'.repeat(2048))"}"#));
+    assert!(doc["error"].as_str().unwrap().contains("exceeds 1024 bytes"));
+}
+
+#[test]
+fn markdown_is_rendered_per_platform_and_sanitized() {
+    let text = "# Hi\\\\n\\\\n**bold** @everyone [site](https://e.com) [x](javascript:alert(1))";
+    let render = |platform: &str| {
+        let script = format!(r#"{{"script":"format.renderMarkdown(\"{}\", {{ platform: '{}' }})"}}"#, text, platform);
+        result_document(&run(&[], &script))["result"].clone()
+    };
+    assert_eq!(render("discord"), "# Hi\n\n**bold** @\u{200b}everyone [site](<https://e.com>) x");
+    assert_eq!(render("slack"), "*Hi*\n\n*bold* @everyone <https://e.com|site> x");
+    assert_eq!(render("plain"), "Hi\n\nbold @\u{200b}everyone site (https://e.com) x");
+    let script = r#"['discord', 'slack', 'plain'].map((platform) => format.renderMarkdown('- one\n\n- two\n\n1. three', { platform })).join('|')"#;
+    let doc = result_document(&run(&[], &serde_json::json!({ "script": script }).to_string()));
+    assert_eq!(doc["result"], ["- one\n- two\n\n1. three"; 3].join("|"));
+}
+
+#[test]
+fn search_helpers_match_commands() {
+    let script = r#"JSON.stringify([search.distance('kitten', 'sitting'), search.find('ＰＩＮＧ', ['Ping', 'pong', 'xping']), search.fuzzy('hepl', ['help', 'hello', 'ban'], { limit: 1 }).map(m => m.item)])"#;
+    let doc = result_document(&run(&[], &serde_json::json!({ "script": script }).to_string()));
+    assert_eq!(doc["result"], r#"[3,[0,2],["help"]]"#);
+    let script = r#"JSON.stringify([search.distance('hepl', 'help'), search.distance('ca', 'abc'), search.distance('', 'abc')])"#;
+    let doc = result_document(&run(&[], &serde_json::json!({ "script": script }).to_string()));
+    assert_eq!(doc["result"], "[1,3,3]");
+    let script = r#"try { search.distance('a'.repeat(1e5), 'b'.repeat(1e5)) } catch (e) { e.name }"#;
+    let doc = result_document(&run(&[], &serde_json::json!({ "script": script }).to_string()));
+    assert_eq!(doc["result"], "RangeError");
+}
+
+#[test]
+fn calc_uses_decimals_and_converts_units() {
+    let script = r#"[calc.eval('0.1 + 0.2'), calc.eval('3 ft + 2 in to cm'), calc.eval('100 c -> f'), (() => { try { calc.eval('1 m to kg') } catch (e) { return e.message } })()].join('|')"#;
+    let doc = result_document(&run(&[], &serde_json::json!({ "script": script }).to_string()));
+    assert_eq!(doc["result"], "0.3|96.52 cm|212 f|calc: cannot convert to kg");
+    let script = r#"[calc.eval('-2^2'), calc.eval('2^-1'), calc.eval('2^3^2')].join('|')"#;
+    let doc = result_document(&run(&[], &serde_json::json!({ "script": script }).to_string()));
+    assert_eq!(doc["result"], "-4|0.5|512");
+    let script = r#"['('.repeat(1e6) + '1', '-'.repeat(1e6) + '1', '2^'.repeat(1e6) + '1'].map((e) => { try { return calc.eval(e) } catch (e) { return e.message } }).join('|')"#;
+    let doc = result_document(&run(&[], &serde_json::json!({ "script": script }).to_string()));
+    assert_eq!(doc["result"], ["calc: expression nested too deeply"; 3].join("|"));
+    let script = r#"(() => { try { return calc.eval('2^-9223372036854775808') } catch (e) { return e.message } })()"#;
+    let doc = result_document(&run(&[], &serde_json::json!({ "script": script }).to_string()));
+    assert_eq!(doc["result"], "calc: exponent too large");
+}
+
+#[cfg(feature = "chart")]
+#[test]
+fn charts_render_to_base64_png() {
+    let script = r#"const png = chart.render({ type: 'bar', values: [3, 1, 4], labels: ['a', 'b', 'c'] }); [png.slice(0, 8), (() => { try { chart.render({ values: [], width: 10 }) } catch (e) { return e.message } })()].join('|')"#;
+    let doc = result_document(&run(&[], &serde_json::json!({ "script": script }).to_string()));
+    assert_eq!(doc["result"], "iVBORw0K|chart: values must have between 1 and 500 points");
+    let script = r##"let defs = '<rect id="a0" width="1" height="1"/>';
+        for (let i = 1; i < 30; i++) defs += `<g id="a${i}"><use href="#a${i - 1}"/><use href="#a${i - 1}"/></g>`;
+        try { chart.render(`<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><defs>${defs}</defs><use href="#a29"/></svg>`) } catch (e) { e.message }"##;
+    let doc = result_document(&run(&[], &serde_json::json!({ "script": script }).to_string()));
+    assert_eq!(doc["result"], "chart: <use> elements are not supported");
+}